@@ -0,0 +1,171 @@
+// Bech32/bech32m codec (BIP-173, BIP-350). Chia encodes payloads well beyond
+// the 90-character limit of BIP-173 (offer IDs, DIDs, ...), so strings may be
+// up to `MAX_LENGTH` characters long instead.
+
+/// Upper bound on the length of an encoded string.
+pub const MAX_LENGTH: usize = 2048;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_LENGTH: usize = 6;
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(self) -> u32 {
+        match self {
+            Variant::Bech32 => BECH32_CONST,
+            Variant::Bech32m => BECH32M_CONST,
+        }
+    }
+}
+
+fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut chk = 1u32;
+    for v in values {
+        let top = chk >> 25;
+        chk = (chk & 0x01ff_ffff) << 5 ^ u32::from(v);
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    hrp.iter()
+        .map(|c| c >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.iter().map(|c| c & 0x1f))
+}
+
+/// Encodes `data` with the (lowercased) `hrp`. The HRP itself is expected to
+/// have been validated by the caller.
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> anyhow::Result<String> {
+    let hrp = hrp.to_ascii_lowercase();
+    let length = hrp.len() + 1 + (data.len() * 8 + 4) / 5 + CHECKSUM_LENGTH;
+    if length > MAX_LENGTH {
+        anyhow::bail!(
+            "bech32 string would be {} chars long, at most {} are allowed",
+            length,
+            MAX_LENGTH
+        );
+    }
+
+    let mut values = Vec::with_capacity(length);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &byte in data {
+        acc = acc << 8 | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            values.push((acc >> bits) as u8 & 0x1f);
+        }
+    }
+    if bits > 0 {
+        values.push((acc << (5 - bits)) as u8 & 0x1f);
+    }
+
+    let checksum = polymod(
+        hrp_expand(hrp.as_bytes())
+            .chain(values.iter().copied())
+            .chain([0; CHECKSUM_LENGTH].iter().copied()),
+    ) ^ variant.constant();
+    for i in 0..CHECKSUM_LENGTH {
+        values.push((checksum >> (5 * (CHECKSUM_LENGTH - 1 - i))) as u8 & 0x1f);
+    }
+
+    let mut out = hrp;
+    out.push('1');
+    out.extend(values.iter().map(|&v| CHARSET[v as usize] as char));
+    Ok(out)
+}
+
+/// Decodes a bech32 or bech32m string into its lowercase HRP, the payload
+/// bytes and the checksum variant it was encoded with.
+pub fn decode(s: &str) -> anyhow::Result<(String, Vec<u8>, Variant)> {
+    decode_max(s, MAX_LENGTH)
+}
+
+/// Like `decode`, for strings up to `max_length` characters long, e.g. offers,
+/// which commonly exceed `MAX_LENGTH`.
+pub fn decode_max(s: &str, max_length: usize) -> anyhow::Result<(String, Vec<u8>, Variant)> {
+    if s.len() > max_length {
+        anyhow::bail!(
+            "bech32 string too long ({} chars, at most {})",
+            s.len(),
+            max_length
+        );
+    }
+    if let Some((position, c)) = s
+        .chars()
+        .enumerate()
+        .find(|(_, c)| !(33..=126).contains(&(*c as u32)))
+    {
+        anyhow::bail!("invalid bech32 character {:?} at position {}", c, position);
+    }
+    if s.bytes().any(|c| c.is_ascii_lowercase()) && s.bytes().any(|c| c.is_ascii_uppercase()) {
+        anyhow::bail!("mixed case bech32 string");
+    }
+    let s = s.to_ascii_lowercase();
+    let separator = match s.rfind('1') {
+        Some(0) => anyhow::bail!("empty bech32 HRP"),
+        Some(i) => i,
+        None => anyhow::bail!("missing bech32 separator '1'"),
+    };
+    let (hrp, rest) = s.split_at(separator);
+    let rest = &rest[1..];
+    if rest.len() < CHECKSUM_LENGTH {
+        anyhow::bail!("bech32 string too short for a checksum");
+    }
+
+    let mut values = Vec::with_capacity(rest.len());
+    for (i, c) in rest.bytes().enumerate() {
+        match CHARSET.iter().position(|&x| x == c) {
+            Some(v) => values.push(v as u8),
+            None => anyhow::bail!(
+                "invalid bech32 character {:?} at position {}",
+                c as char,
+                separator + 1 + i
+            ),
+        }
+    }
+
+    let variant = match polymod(hrp_expand(hrp.as_bytes()).chain(values.iter().copied())) {
+        BECH32_CONST => Variant::Bech32,
+        BECH32M_CONST => Variant::Bech32m,
+        _ => anyhow::bail!("invalid bech32 checksum"),
+    };
+
+    values.truncate(values.len() - CHECKSUM_LENGTH);
+    let mut data = Vec::with_capacity(values.len() * 5 / 8);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for v in values {
+        acc = acc << 5 | u32::from(v);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+        }
+    }
+    if bits >= 5 || acc & ((1 << bits) - 1) != 0 {
+        anyhow::bail!("invalid bech32 padding");
+    }
+    Ok((hrp.to_string(), data, variant))
+}
@@ -0,0 +1,125 @@
+// Tree hashing straight off the CLVM serialization format, so programs never
+// need to be materialized in an allocator. An explicit stack keeps deeply
+// nested programs from overflowing the call stack.
+
+use sha2::{Digest, Sha256};
+
+const CONS_BOX_MARKER: u8 = 0xff;
+const BACK_REFERENCE: u8 = 0xfe;
+const MAX_SINGLE_BYTE: u8 = 0x7f;
+
+enum Op {
+    Parse,
+    Cons,
+}
+
+/// Returns the hash of an atom, `sha256(1 || atom)`.
+pub fn hash_atom(atom: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(atom);
+    hasher.finalize().into()
+}
+
+/// Returns the hash of a pair, `sha256(2 || first || rest)`.
+pub fn hash_pair(first: &[u8; 32], rest: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([2]);
+    hasher.update(first);
+    hasher.update(rest);
+    hasher.finalize().into()
+}
+
+/// Returns the tree hash of a module curried with arguments, given their tree
+/// hashes, i.e. of `(a (q . mod) (c (q . arg1) (c (q . arg2) ... 1)))`.
+#[cfg(feature = "bls")]
+pub fn curry_tree_hash(mod_hash: &[u8; 32], arg_hashes: &[[u8; 32]]) -> [u8; 32] {
+    let quote = hash_atom(&[1]);
+    let apply = hash_atom(&[2]);
+    let cons = hash_atom(&[4]);
+    let nil = hash_atom(&[]);
+    let mut args = hash_atom(&[1]);
+    for arg in arg_hashes.iter().rev() {
+        let quoted_arg = hash_pair(&quote, arg);
+        args = hash_pair(&cons, &hash_pair(&quoted_arg, &hash_pair(&args, &nil)));
+    }
+    let quoted_mod = hash_pair(&quote, mod_hash);
+    hash_pair(&apply, &hash_pair(&quoted_mod, &hash_pair(&args, &nil)))
+}
+
+/// Computes the tree hash of a serialized CLVM program.
+pub fn tree_hash(program: &[u8]) -> anyhow::Result<[u8; 32]> {
+    let mut pos = 0;
+    let mut ops = vec![Op::Parse];
+    let mut hashes: Vec<[u8; 32]> = Vec::new();
+    while let Some(op) = ops.pop() {
+        match op {
+            Op::Parse => {
+                let byte = *program
+                    .get(pos)
+                    .ok_or_else(|| end_of_program(program.len()))?;
+                pos += 1;
+                match byte {
+                    CONS_BOX_MARKER => {
+                        ops.push(Op::Cons);
+                        ops.push(Op::Parse);
+                        ops.push(Op::Parse);
+                    }
+                    BACK_REFERENCE => {
+                        anyhow::bail!(
+                            "CLVM back references are not supported (at offset {})",
+                            pos - 1
+                        )
+                    }
+                    0..=MAX_SINGLE_BYTE => hashes.push(hash_atom(&[byte])),
+                    _ => {
+                        let len = atom_length(program, &mut pos, byte)?;
+                        let atom = program
+                            .get(pos..pos.saturating_add(len))
+                            .ok_or_else(|| end_of_program(program.len()))?;
+                        pos += len;
+                        hashes.push(hash_atom(atom));
+                    }
+                }
+            }
+            Op::Cons => {
+                let rest = hashes.pop().expect("parsed rest");
+                let first = hashes.pop().expect("parsed first");
+                hashes.push(hash_pair(&first, &rest));
+            }
+        }
+    }
+    if pos != program.len() {
+        anyhow::bail!(
+            "trailing bytes after CLVM program ({} of {} bytes used)",
+            pos,
+            program.len()
+        );
+    }
+    Ok(hashes.pop().expect("parsed program"))
+}
+
+/// Decodes the size prefix of an atom whose first byte is `first`; the number
+/// of leading one bits gives the number of prefix bytes.
+fn atom_length(program: &[u8], pos: &mut usize, first: u8) -> anyhow::Result<usize> {
+    let prefix_len = first.leading_ones() as usize;
+    if prefix_len > 6 {
+        anyhow::bail!("invalid CLVM atom size prefix {:#04x}", first);
+    }
+    let mut len = u64::from(first & (0xff >> prefix_len));
+    for _ in 1..prefix_len {
+        let byte = *program
+            .get(*pos)
+            .ok_or_else(|| end_of_program(program.len()))?;
+        *pos += 1;
+        len = len << 8 | u64::from(byte);
+    }
+    if len >= 0x4_0000_0000 {
+        anyhow::bail!("CLVM atom too large ({} bytes)", len);
+    }
+    Ok(len as usize)
+}
+
+fn end_of_program(len: usize) -> anyhow::Error {
+    anyhow::anyhow!("unexpected end of CLVM program after {} bytes", len)
+}
@@ -0,0 +1,223 @@
+// The messages AGG_SIG_* conditions require signatures of, and checking a
+// spend bundle's aggregated signature against the conditions its puzzles
+// output. Except for AGG_SIG_UNSAFE, messages are suffixed with coin details
+// and per-opcode data derived from the network's genesis challenge, so that
+// signatures can't be replayed on other coins or networks.
+
+use sha2::{Digest, Sha256};
+
+pub const AGG_SIG_PARENT: u8 = 43;
+pub const AGG_SIG_PUZZLE: u8 = 44;
+pub const AGG_SIG_AMOUNT: u8 = 45;
+pub const AGG_SIG_PUZZLE_AMOUNT: u8 = 46;
+pub const AGG_SIG_PARENT_AMOUNT: u8 = 47;
+pub const AGG_SIG_PARENT_PUZZLE: u8 = 48;
+pub const AGG_SIG_UNSAFE: u8 = 49;
+pub const AGG_SIG_ME: u8 = 50;
+
+pub const MAINNET_GENESIS_CHALLENGE: [u8; 32] = [
+    0xcc, 0xd5, 0xbb, 0x71, 0x18, 0x35, 0x32, 0xbf, 0xf2, 0x20, 0xba, 0x46, 0xc2, 0x68, 0x99, 0x1a,
+    0x3f, 0xf0, 0x7e, 0xb3, 0x58, 0xe8, 0x25, 0x5a, 0x65, 0xc3, 0x0a, 0x2d, 0xce, 0x0e, 0x5f, 0xbb,
+];
+pub const TESTNET11_GENESIS_CHALLENGE: [u8; 32] = [
+    0x37, 0xa9, 0x0e, 0xb5, 0x18, 0x5a, 0x9c, 0x44, 0x39, 0xa9, 0x1d, 0xdc, 0x98, 0xbb, 0xad, 0xce,
+    0x7b, 0x4f, 0xeb, 0xa0, 0x60, 0xd5, 0x01, 0x16, 0xa0, 0x67, 0xde, 0x66, 0xbf, 0x23, 0x66, 0x15,
+];
+
+/// Upper bound on the cost of running a puzzle, the maximum cost of a block.
+#[cfg(feature = "clvm")]
+const MAX_COST: u64 = 11_000_000_000;
+
+/// Rules puzzles are run under, those of chia-blockchain's mempool rather than
+/// the more lenient ones of past blocks: unknown operators fail instead of
+/// being no-ops with a cost, and so does division of negative numbers.
+#[cfg(feature = "clvm")]
+const DIALECT_FLAGS: u32 = clvmr::chia_dialect::NO_UNKNOWN_OPS | clvmr::chia_dialect::NO_NEG_DIV;
+
+pub struct Coin {
+    pub parent_coin_info: [u8; 32],
+    pub puzzle_hash: [u8; 32],
+    pub amount: u64,
+}
+
+impl Coin {
+    pub fn coin_id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.parent_coin_info);
+        hasher.update(self.puzzle_hash);
+        hasher.update(amount_atom(self.amount));
+        hasher.finalize().into()
+    }
+}
+
+/// Returns the bytes an AGG_SIG_* condition with `opcode` and `msg` requires
+/// a signature of when output by the puzzle of `coin`, or None if `opcode`
+/// isn't an AGG_SIG_* condition.
+pub fn agg_sig_message(
+    opcode: u8,
+    msg: &[u8],
+    coin: &Coin,
+    genesis_challenge: &[u8; 32],
+) -> Option<Vec<u8>> {
+    let parent = &coin.parent_coin_info[..];
+    let puzzle_hash = &coin.puzzle_hash[..];
+    let amount = amount_atom(coin.amount);
+    let parts = match opcode {
+        AGG_SIG_PARENT => vec![parent],
+        AGG_SIG_PUZZLE => vec![puzzle_hash],
+        AGG_SIG_AMOUNT => vec![&amount[..]],
+        AGG_SIG_PUZZLE_AMOUNT => vec![puzzle_hash, &amount[..]],
+        AGG_SIG_PARENT_AMOUNT => vec![parent, &amount[..]],
+        AGG_SIG_PARENT_PUZZLE => vec![parent, puzzle_hash],
+        AGG_SIG_UNSAFE => return Some(msg.to_vec()),
+        AGG_SIG_ME => return Some([msg, &coin.coin_id()[..], &genesis_challenge[..]].concat()),
+        _ => return None,
+    };
+    // The conditions added in the 2.0 hard fork each sign different data.
+    let mut hasher = Sha256::new();
+    hasher.update(genesis_challenge);
+    hasher.update([opcode]);
+    let additional_data: [u8; 32] = hasher.finalize().into();
+    let mut message = msg.to_vec();
+    for part in parts {
+        message.extend_from_slice(part);
+    }
+    message.extend_from_slice(&additional_data);
+    Some(message)
+}
+
+/// Encodes an amount as CLVM atom, as conditions and coin IDs use it: without
+/// leading zero bytes, except one to keep the high bit clear.
+fn amount_atom(amount: u64) -> Vec<u8> {
+    let bytes = amount.to_be_bytes();
+    let start = (amount.leading_zeros() / 8) as usize;
+    let mut atom = Vec::with_capacity(9);
+    if bytes.get(start).map_or(false, |b| b & 0x80 != 0) {
+        atom.push(0);
+    }
+    atom.extend_from_slice(&bytes[start..]);
+    atom
+}
+
+/// Checks the aggregated signature of a serialized spend bundle against the
+/// AGG_SIG_* conditions of all its coin spends. Returns false for invalid
+/// signatures, malformed AGG_SIG_* conditions and invalid public keys
+/// (including the point at infinity), and an error for malformed bundles,
+/// puzzle reveals not matching their coin and puzzles that fail to run under
+/// `DIALECT_FLAGS`.
+#[cfg(feature = "clvm")]
+pub fn verify_spend_bundle(bundle: &[u8], genesis_challenge: &[u8; 32]) -> anyhow::Result<bool> {
+    use anyhow::Context as _;
+    use clvmr::allocator::Allocator;
+    use clvmr::chia_dialect::ChiaDialect;
+    use clvmr::reduction::Reduction;
+    use clvmr::run_program::run_program;
+    use clvmr::serde::node_from_stream;
+    use std::convert::TryFrom;
+    use std::io::Cursor;
+
+    let mut cursor = Cursor::new(bundle);
+    let count = u32::from_be_bytes(read(&mut cursor)?);
+    let mut data = Vec::new();
+    for i in 1..=count {
+        let coin = Coin {
+            parent_coin_info: read(&mut cursor)?,
+            puzzle_hash: read(&mut cursor)?,
+            amount: u64::from_be_bytes(read(&mut cursor)?),
+        };
+        let mut a = Allocator::new();
+        let start = cursor.position() as usize;
+        let puzzle = node_from_stream(&mut a, &mut cursor)
+            .with_context(|| format!("invalid puzzle reveal in coin spend {}", i))?;
+        let reveal = &bundle[start..cursor.position() as usize];
+        if crate::clvm::tree_hash(reveal)? != coin.puzzle_hash {
+            anyhow::bail!(
+                "puzzle reveal of coin spend {} doesn't match the coin's puzzle hash",
+                i
+            );
+        }
+        let solution = node_from_stream(&mut a, &mut cursor)
+            .with_context(|| format!("invalid solution in coin spend {}", i))?;
+        let Reduction(_cost, mut conditions) = run_program(
+            &mut a,
+            &ChiaDialect::new(DIALECT_FLAGS),
+            puzzle,
+            solution,
+            MAX_COST,
+        )
+        .map_err(|e| anyhow::anyhow!("puzzle of coin spend {} failed: {}", i, e.1))?;
+        while let Some((condition, rest)) = pair(&a, conditions) {
+            conditions = rest;
+            let (opcode, args) = match pair(&a, condition) {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let opcode = match atom(&a, opcode) {
+                Some(&[opcode]) if (AGG_SIG_PARENT..=AGG_SIG_ME).contains(&opcode) => opcode,
+                _ => continue,
+            };
+            let (pk, msg) = match pair(&a, args)
+                .and_then(|(pk, rest)| Some((atom(&a, pk)?, atom(&a, pair(&a, rest)?.0)?)))
+            {
+                Some(pk_and_msg) => pk_and_msg,
+                None => return Ok(false),
+            };
+            // Like consensus, the point at infinity isn't accepted as key.
+            let pk = match <&[u8; 48]>::try_from(pk)
+                .ok()
+                .filter(|bytes| !crate::is_bls_infinity(&bytes[..]))
+                .and_then(|bytes| chia_bls::PublicKey::from_bytes(bytes).ok())
+            {
+                Some(pk) => pk,
+                None => return Ok(false),
+            };
+            let message =
+                agg_sig_message(opcode, msg, &coin, genesis_challenge).expect("AGG_SIG opcode");
+            data.push((pk, message));
+        }
+    }
+    let sig: [u8; 96] = read(&mut cursor)?;
+    if cursor.position() as usize != bundle.len() {
+        anyhow::bail!(
+            "trailing bytes after spend bundle ({} of {} bytes used)",
+            cursor.position(),
+            bundle.len()
+        );
+    }
+    Ok(match chia_bls::Signature::from_bytes(&sig) {
+        Ok(sig) => chia_bls::aggregate_verify(&sig, data.iter().map(|(pk, msg)| (pk, msg))),
+        Err(_) => false,
+    })
+}
+
+#[cfg(feature = "clvm")]
+fn read<const N: usize>(cursor: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<[u8; N]> {
+    use std::io::Read;
+    let mut bytes = [0; N];
+    cursor.read_exact(&mut bytes).map_err(|_| {
+        anyhow::anyhow!(
+            "unexpected end of spend bundle after {} bytes",
+            cursor.get_ref().len()
+        )
+    })?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "clvm")]
+fn pair(
+    a: &clvmr::allocator::Allocator,
+    node: clvmr::allocator::NodePtr,
+) -> Option<(clvmr::allocator::NodePtr, clvmr::allocator::NodePtr)> {
+    match a.sexp(node) {
+        clvmr::allocator::SExp::Pair(first, rest) => Some((first, rest)),
+        clvmr::allocator::SExp::Atom(_) => None,
+    }
+}
+
+#[cfg(feature = "clvm")]
+fn atom(a: &clvmr::allocator::Allocator, node: clvmr::allocator::NodePtr) -> Option<&[u8]> {
+    match a.sexp(node) {
+        clvmr::allocator::SExp::Atom(_) => Some(a.atom(node)),
+        clvmr::allocator::SExp::Pair(..) => None,
+    }
+}
@@ -0,0 +1,85 @@
+// Table-driven hex codec. `ctx.get_raw()` hands us borrowed bytes, so both
+// directions work on byte slices and only allocate the output.
+
+const INVALID: u8 = 0xff;
+const SKIP: u8 = 0xfe;
+
+static DECODE: [u8; 256] = decode_table();
+static LOWER: &[u8; 16] = b"0123456789abcdef";
+static UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+const fn decode_table() -> [u8; 256] {
+    let mut table = [INVALID; 256];
+    let mut i = 0;
+    while i < 10 {
+        table[b'0' as usize + i] = i as u8;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 6 {
+        table[b'a' as usize + i] = 10 + i as u8;
+        table[b'A' as usize + i] = 10 + i as u8;
+        i += 1;
+    }
+    // Same set as `u8::is_ascii_whitespace`.
+    table[b' ' as usize] = SKIP;
+    table[b'\t' as usize] = SKIP;
+    table[b'\n' as usize] = SKIP;
+    table[b'\r' as usize] = SKIP;
+    table[0x0c] = SKIP;
+    table
+}
+
+/// Decodes a hex string into bytes.
+///
+/// Digits may be upper- or lowercase, an optional leading '0x' or '0X' is
+/// skipped and ASCII whitespace (including newlines) is ignored. Positions in
+/// error messages are 0-based character offsets into the original string.
+pub fn decode(input: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut start = input
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(input.len());
+    if input[start..].starts_with(b"0x") || input[start..].starts_with(b"0X") {
+        start += 2;
+    }
+    let digits = &input[start..];
+    let mut out = Vec::with_capacity(digits.len() / 2);
+    let mut pending = None;
+    for (i, &c) in digits.iter().enumerate() {
+        let nibble = DECODE[c as usize];
+        if nibble < 16 {
+            match pending.take() {
+                None => pending = Some(nibble),
+                Some(high) => out.push(high << 4 | nibble),
+            }
+        } else if nibble == INVALID {
+            return Err(invalid_character(input, start + i));
+        }
+    }
+    if pending.is_some() {
+        anyhow::bail!("odd-length hex string ({} chars)", out.len() * 2 + 1);
+    }
+    Ok(out)
+}
+
+fn invalid_character(input: &[u8], offset: usize) -> anyhow::Error {
+    let position = String::from_utf8_lossy(&input[..offset]).chars().count();
+    let c = String::from_utf8_lossy(&input[offset..])
+        .chars()
+        .next()
+        .unwrap_or(char::REPLACEMENT_CHARACTER);
+    anyhow::anyhow!("invalid hex character {:?} at position {}", c, position)
+}
+
+/// Hex-encodes bytes, optionally with uppercase digits, after `prefix`.
+pub fn encode(data: &[u8], prefix: &str, upper: bool) -> String {
+    let digits = if upper { UPPER } else { LOWER };
+    let mut out = Vec::with_capacity(prefix.len() + data.len() * 2);
+    out.extend_from_slice(prefix.as_bytes());
+    for &byte in data {
+        out.push(digits[(byte >> 4) as usize]);
+        out.push(digits[(byte & 0x0f) as usize]);
+    }
+    String::from_utf8(out).expect("hex digits are ASCII")
+}
@@ -1,188 +1,7469 @@
-use std::convert::TryInto;
+use std::convert::TryFrom;
 use std::io::Cursor;
 
 use rusqlite::functions::{Context, FunctionFlags};
-use rusqlite::types::{ToSqlOutput, Value};
+use rusqlite::types::{ToSqlOutput, Type, Value, ValueRef};
 
+mod bech32;
+mod clvm;
+mod conditions;
 #[cfg(feature = "build_extension")]
 mod ext;
+mod hex;
+mod merkle_set;
 
 fn ah(e: anyhow::Error) -> rusqlite::Error {
     rusqlite::Error::UserFunctionError(format!("{:?}", e).into())
 }
 
 fn setup(db: &rusqlite::Connection) -> anyhow::Result<()> {
-    create_functions(&db)
+    create_functions(&db)?;
+    create_collations(&db)?;
+    create_modules(&db)
+}
+
+fn create_modules(db: &rusqlite::Connection) -> anyhow::Result<()> {
+    use rusqlite::vtab::eponymous_only_module;
+    db.create_module(
+        "chia_reward_schedule",
+        eponymous_only_module::<RewardScheduleTab>(),
+        None,
+    )?;
+    Ok(())
+}
+
+fn create_collations(db: &rusqlite::Connection) -> anyhow::Result<()> {
+    // Bech32 strings are case-insensitive, and only consist of ASCII.
+    db.create_collation("CHIA_ADDRESS", |a, b| {
+        let lowercase = |s: &str| {
+            s.bytes()
+                .map(|b| b.to_ascii_lowercase())
+                .collect::<Vec<_>>()
+        };
+        lowercase(a).cmp(&lowercase(b))
+    })?;
+    Ok(())
 }
 
 fn create_functions(db: &rusqlite::Connection) -> anyhow::Result<()> {
     let flags = FunctionFlags::SQLITE_UTF8
         | FunctionFlags::SQLITE_INNOCUOUS
         | FunctionFlags::SQLITE_DETERMINISTIC;
-    db.create_scalar_function("bech32m_encode", 2, flags, |ctx| {
-        bech32m_encode_fn(ctx).map_err(ah)
+    for n_arg in 1..=2 {
+        db.create_scalar_function("base64_encode", n_arg, flags, |ctx| {
+            base64_encode_fn(ctx).map_err(ah)
+        })?;
+        db.create_scalar_function("base64_decode", n_arg, flags, |ctx| {
+            base64_decode_fn(ctx).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("base58_encode", 1, flags, |ctx| {
+        base58_encode_fn(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("base58_decode", 1, flags, |ctx| {
+        base58_decode_fn(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("base58check_encode", 1, flags, |ctx| {
+        base58check_encode_fn(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("base58check_decode", 1, flags, |ctx| {
+        base58check_decode_fn(ctx).map_err(ah)
+    })?;
+    create_bech32m_encode(db, flags, BECH32M_ENCODE_CACHE_SIZE)?;
+    for n_arg in 1..=3 {
+        db.create_scalar_function("bech32_decode", n_arg, flags, |ctx| {
+            bech32_decode_fn(ctx).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("bech32_encode", 2, flags, |ctx| {
+        bech32_encode_fn(ctx).map_err(ah)
+    })?;
+    for n_arg in 1..=3 {
+        db.create_scalar_function("bech32m_decode", n_arg, flags, |ctx| {
+            bech32m_decode_fn(ctx).map_err(ah)
+        })?;
+    }
+    for n_arg in 1..=3 {
+        db.create_scalar_function("bech32m_try_encode", n_arg, flags, |ctx| {
+            Ok(bech32m_encode_fn(ctx).unwrap_or_else(|_| null()))
+        })?;
+    }
+    for name in &["bech32m_try_decode", "try_bech32m_decode"] {
+        for n_arg in 1..=3 {
+            db.create_scalar_function(name, n_arg, flags, |ctx| {
+                Ok(bech32m_decode_fn(ctx).unwrap_or_else(|_| null()))
+            })?;
+        }
+    }
+    db.create_scalar_function("bech32m_hrp", 1, flags, |ctx| {
+        bech32m_hrp_fn(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("bech32_variant", 1, flags, |ctx| {
+        bech32_variant_fn(ctx).map_err(ah)
+    })?;
+    for n_arg in 1..=2 {
+        db.create_scalar_function("address_to_puzzle_hash", n_arg, flags, |ctx| {
+            address_to_puzzle_hash(ctx).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("puzzle_hash_to_address", 2, flags, |ctx| {
+        puzzle_hash_to_address(ctx).map_err(ah)
+    })?;
+    for n_arg in 1..=2 {
+        db.create_scalar_function("chia_address_valid", n_arg, flags, |ctx| {
+            Ok(chia_address_valid(ctx))
+        })?;
+    }
+    db.create_scalar_function("chia_address_network", 1, flags, |ctx| {
+        Ok(chia_address_network(ctx))
+    })?;
+    db.create_scalar_function("chia_bech32_kind", 1, flags, |ctx| {
+        Ok(chia_bech32_kind(ctx))
+    })?;
+    let bitwise_ops: [(&str, fn(u8, u8) -> u8); 3] = [
+        ("blob_and", |a, b| a & b),
+        ("blob_or", |a, b| a | b),
+        ("blob_xor", |a, b| a ^ b),
+    ];
+    for &(name, op) in &bitwise_ops {
+        for n_arg in 2..=3 {
+            db.create_scalar_function(name, n_arg, flags, move |ctx| {
+                blob_bitwise_fn(ctx, op).map_err(ah)
+            })?;
+        }
+    }
+    db.create_scalar_function("blob_not", 1, flags, |ctx| blob_not_fn(ctx).map_err(ah))?;
+    db.create_scalar_function("be_uint", 1, flags, |ctx| be_uint_fn(ctx).map_err(ah))?;
+    #[cfg(feature = "blake2")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("blake2b_256", n_arg, flags, |ctx| {
+            blake2b_256(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "blake3")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("blake3", n_arg, flags, |ctx| blake3_fn(ctx).map_err(ah))?;
+    }
+    db.create_scalar_function("bls_fingerprint", 1, flags, |ctx| {
+        bls_fingerprint(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "bls")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("bls_pubkey_valid", n_arg, flags, |ctx| {
+            bls_pubkey_valid(ctx).map_err(ah)
+        })?;
+        db.create_scalar_function("bls_signature_valid", n_arg, flags, |ctx| {
+            bls_signature_valid(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "bls")]
+    db.create_aggregate_function("bls_aggregate_pks", 1, flags, BlsAggregatePks)?;
+    #[cfg(feature = "bls")]
+    db.create_aggregate_function("bls_aggregate_sigs", 1, flags, BlsAggregateSigs)?;
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("bls_aggregate_verify", 2, flags, |ctx| {
+        bls_aggregate_verify(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("bls_verify", 3, flags, |ctx| bls_verify(ctx).map_err(ah))?;
+    #[cfg(feature = "secp")]
+    for &(name, verify) in &[
+        ("secp256k1_verify", secp256k1_verify as SecpVerify),
+        ("secp256r1_verify", secp256r1_verify),
+    ] {
+        db.create_scalar_function(name, 3, flags, move |ctx| {
+            secp_verify(ctx, verify).map_err(ah)
+        })?;
+    }
+    // Secret keys shouldn't be derivable from triggers and views of untrusted
+    // schemas, so unlike everything else these aren't innocuous.
+    #[cfg(feature = "bls")]
+    let secret_flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    #[cfg(feature = "bls")]
+    for &(name, hardened) in &[
+        ("bls_derive_child_sk", true),
+        ("bls_derive_child_sk_unhardened", false),
+    ] {
+        db.create_scalar_function(name, 2, secret_flags, move |ctx| {
+            bls_derive_child_sk(ctx, hardened).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("bls_derive_child_pk_unhardened", 2, flags, |ctx| {
+        bls_derive_child_pk_unhardened(ctx).map_err(ah)
     })?;
-    db.create_scalar_function("bech32m_decode", 1, flags, |ctx| {
-        bech32m_decode_fn(ctx).map_err(ah)
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("chia_observer_pk", 2, flags, |ctx| {
+        chia_observer_pk(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("chia_address_for_pk", 2, flags, |ctx| {
+        chia_address_for_pk(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("chia_puzzle_hash_for_pk", 1, flags, |ctx| {
+        chia_puzzle_hash_for_pk(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "bls")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("chia_synthetic_pk", n_arg, flags, |ctx| {
+            chia_synthetic_pk(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("bls_sk_to_pk", 1, secret_flags, |ctx| {
+        bls_sk_to_pk(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "bls")]
+    for n_arg in 2..=3 {
+        db.create_scalar_function("chia_wallet_pk", n_arg, secret_flags, |ctx| {
+            chia_wallet_pk(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "bls")]
+    for name in &["chia_master_sk_from_seed", "bls_keygen"] {
+        db.create_scalar_function(name, 1, secret_flags, |ctx| {
+            chia_master_sk_from_seed(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "bls")]
+    db.create_scalar_function("bls_sign", 2, secret_flags, |ctx| bls_sign(ctx).map_err(ah))?;
+    #[cfg(feature = "bls")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("chia_mnemonic_to_seed", n_arg, secret_flags, |ctx| {
+            chia_mnemonic_to_seed(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "brotli")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("brotli_compress_blob", n_arg, flags, |ctx| {
+            brotli_compress_blob(ctx).map_err(ah)
+        })?;
+        db.create_scalar_function("brotli_decompress_blob", n_arg, flags, |ctx| {
+            brotli_decompress_blob(ctx).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("blob_concat", -1, flags, |ctx| {
+        blob_concat_fn(ctx).map_err(ah)
     })?;
     db.create_scalar_function("blob_from_hex", 1, flags, |ctx| {
         blob_from_hex_fn(ctx).map_err(ah)
     })?;
+    db.create_scalar_function("blob_reverse", 1, flags, |ctx| {
+        blob_reverse_fn(ctx).map_err(ah)
+    })?;
+    for n_arg in 2..=3 {
+        db.create_scalar_function("blob_slice", n_arg, flags, |ctx| {
+            blob_slice_fn(ctx).map_err(ah)
+        })?;
+    }
+    for n_arg in 1..=2 {
+        db.create_scalar_function("blob_to_hex", n_arg, flags, |ctx| {
+            blob_to_hex_fn(ctx).map_err(ah)
+        })?;
+    }
     db.create_scalar_function("chia_amount_int", 1, flags, |ctx| {
         chia_amount_int(ctx).map_err(ah)
     })?;
+    db.create_scalar_function("chia_amount_blob", 1, flags, |ctx| {
+        chia_amount_blob(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("chia_amount_from_minimal", 1, flags, |ctx| {
+        chia_amount_from_minimal(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("chia_amount_text", 1, flags, |ctx| {
+        chia_amount_text(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("chia_amount_uint", 1, flags, |ctx| {
+        chia_amount_uint(ctx).map_err(ah)
+    })?;
+    for &(unit, decimals) in &[("xch", XCH_DECIMALS), ("cat", CAT_DECIMALS)] {
+        let name = format!("mojo_to_{}", unit);
+        db.create_scalar_function(&name, 1, flags, move |ctx| {
+            mojo_to_amount(ctx, Some(decimals)).map_err(ah)
+        })?;
+        let name = format!("{}_to_mojo", unit);
+        db.create_scalar_function(&name, 1, flags, move |ctx| {
+            amount_to_mojo(ctx, Some(decimals)).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("mojo_to_amount", 2, flags, |ctx| {
+        mojo_to_amount(ctx, None).map_err(ah)
+    })?;
+    db.create_scalar_function("amount_to_mojo", 2, flags, |ctx| {
+        amount_to_mojo(ctx, None).map_err(ah)
+    })?;
+    for n_arg in 1..=3 {
+        db.create_scalar_function("format_mojo", n_arg, flags, |ctx| {
+            format_mojo(ctx).map_err(ah)
+        })?;
+    }
+    for &(name, what) in &[
+        ("chia_coin_announcement_id", "coin ID"),
+        ("chia_puzzle_announcement_id", "puzzle hash"),
+    ] {
+        db.create_scalar_function(name, 2, flags, move |ctx| {
+            announcement_id(ctx, what).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("chia_agg_sig_message", 6, flags, |ctx| {
+        chia_agg_sig_message(ctx).map_err(ah)
+    })?;
+    let rewards: [(&str, fn(u64) -> u128); 3] = [
+        ("chia_block_reward", |height| {
+            let (pool, farmer) = block_rewards(height);
+            u128::from(pool) + u128::from(farmer)
+        }),
+        ("chia_pool_reward", |height| block_rewards(height).0.into()),
+        ("chia_farmer_reward", |height| {
+            block_rewards(height).1.into()
+        }),
+    ];
+    for &(name, reward) in &rewards {
+        db.create_scalar_function(name, 1, flags, move |ctx| {
+            block_reward_fn(ctx, reward).map_err(ah)
+        })?;
+    }
     db.create_scalar_function("chia_fullblock_json", 1, flags, |ctx| {
         chia_fullblock_json(ctx).map_err(ah)
     })?;
-    db.create_scalar_function("sha256sum", 1, flags, |ctx| sha256sum(ctx).map_err(ah))?;
-    db.create_scalar_function("zstd_decompress_blob", 1, flags, |ctx| {
-        zstd_decompress_blob(ctx).map_err(ah)
+    db.create_scalar_function("chia_coin_json", 1, flags, |ctx| {
+        chia_coin_json(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "clvm")]
+    db.create_scalar_function("chia_spendbundle_verify_sig", 2, flags, |ctx| {
+        chia_spendbundle_verify_sig(ctx).map_err(ah)
+    })?;
+    for name in &["clvm_tree_hash", "sha256tree"] {
+        db.create_scalar_function(name, 1, flags, |ctx| clvm_tree_hash_fn(ctx).map_err(ah))?;
+    }
+    db.create_scalar_function("clvm_int_decode", 1, flags, |ctx| {
+        clvm_int_decode_fn(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("clvm_int_encode", 1, flags, |ctx| {
+        clvm_int_encode_fn(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("compression_format", 1, flags, |ctx| {
+        compression_format(ctx).map_err(ah)
+    })?;
+    db.create_scalar_function("crc32", 1, flags, |ctx| crc32(ctx).map_err(ah))?;
+    for name in &["double_sha256", "sha256d"] {
+        db.create_scalar_function(name, 1, flags, |ctx| double_sha256_fn(ctx).map_err(ah))?;
+    }
+    #[cfg(feature = "sha3")]
+    db.create_scalar_function("keccak256", 1, flags, |ctx| keccak256(ctx).map_err(ah))?;
+    #[cfg(feature = "gzip")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("gzip_compress_blob", n_arg, flags, |ctx| {
+            gzip_compress_blob(ctx).map_err(ah)
+        })?;
+        db.create_scalar_function("gzip_decompress_blob", n_arg, flags, |ctx| {
+            gzip_decompress_blob(ctx).map_err(ah)
+        })?;
+        db.create_scalar_function("inflate_blob", n_arg, flags, |ctx| {
+            inflate_blob(ctx).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("hmac_sha256", 2, flags, |ctx| hmac_sha256(ctx).map_err(ah))?;
+    db.create_scalar_function("le_uint", 1, flags, |ctx| le_uint_fn(ctx).map_err(ah))?;
+    #[cfg(feature = "lz4")]
+    db.create_scalar_function("lz4_compress_blob", 1, flags, |ctx| {
+        lz4_compress_blob(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "lz4")]
+    for n_arg in 1..=2 {
+        db.create_scalar_function("lz4_decompress_blob", n_arg, flags, |ctx| {
+            lz4_decompress_blob(ctx).map_err(ah)
+        })?;
+    }
+    db.create_aggregate_function("merkle_set_root", 1, flags, MerkleSetRootAgg)?;
+    for n_arg in 3..=4 {
+        db.create_scalar_function("merkle_set_verify", n_arg, flags, |ctx| {
+            merkle_set_verify(ctx).map_err(ah)
+        })?;
+    }
+    #[cfg(feature = "ripemd")]
+    db.create_scalar_function("hash160", 1, flags, |ctx| hash160(ctx).map_err(ah))?;
+    #[cfg(feature = "ripemd")]
+    db.create_scalar_function("ripemd160", 1, flags, |ctx| ripemd160(ctx).map_err(ah))?;
+    db.create_scalar_function("sha224", 1, flags, |ctx| sha224(ctx).map_err(ah))?;
+    db.create_aggregate_function("sha256_agg", 1, flags, Sha256Agg)?;
+    db.create_aggregate_function("sum_mojo", 1, flags, SumMojoAgg)?;
+    db.create_window_function("running_mojo_balance", 1, flags, RunningMojoBalance)?;
+    // Reads the file system, so neither innocuous nor deterministic, and not
+    // callable from triggers or views.
+    db.create_scalar_function(
+        "sha256_file",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DIRECTONLY,
+        |ctx| sha256_file(ctx).map_err(ah),
+    )?;
+    db.create_scalar_function("sha256_prefix", 2, flags, |ctx| {
+        sha256_prefix(ctx).map_err(ah)
+    })?;
+    #[cfg(feature = "sha3")]
+    db.create_scalar_function("sha3_256", 1, flags, |ctx| sha3_256(ctx).map_err(ah))?;
+    for name in &["sha256sum", "sha256sum_concat"] {
+        db.create_scalar_function(name, -1, flags, |ctx| sha256sum(ctx).map_err(ah))?;
+    }
+    for name in &["sha512sum", "sha512"] {
+        db.create_scalar_function(name, 1, flags, |ctx| sha512sum(ctx).map_err(ah))?;
+    }
+    db.create_scalar_function("uint_to_be_blob", 2, flags, |ctx| {
+        uint_to_be_blob_fn(ctx).map_err(ah)
+    })?;
+    for n_arg in 1..=2 {
+        db.create_scalar_function("xxh3_64", n_arg, flags, |ctx| xxh3_64(ctx).map_err(ah))?;
+    }
+    for n_arg in 1..=3 {
+        db.create_scalar_function("zstd_compress_blob", n_arg, flags, |ctx| {
+            zstd_compress_blob(ctx).map_err(ah)
+        })?;
+    }
+    for n_arg in 1..=2 {
+        db.create_scalar_function("zstd_decompress_blob", n_arg, flags, |ctx| {
+            zstd_decompress_blob(ctx).map_err(ah)
+        })?;
+    }
+    db.create_scalar_function("zstd_frame_content_size", 1, flags, |ctx| {
+        zstd_frame_content_size(ctx).map_err(ah)
     })?;
+    for n_arg in 2..=3 {
+        db.create_scalar_function("zstd_decompress_with_dict", n_arg, flags, |ctx| {
+            zstd_decompress_with_dict(ctx).map_err(ah)
+        })?;
+    }
+    Ok(())
+}
+
+/// Selects the base64 engine from an optional mode argument: 'std' (default)
+/// or 'urlsafe', each optionally suffixed with '_nopad' to omit padding when
+/// encoding. Decoding requires canonical padding only in 'std' mode; the other
+/// modes tolerate missing padding (e.g. from URLs that strip it).
+fn base64_engine(ctx: &Context, idx: usize) -> anyhow::Result<base64::engine::GeneralPurpose> {
+    use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+    let mode = if ctx.len() > idx {
+        ctx.get::<String>(idx)?
+    } else {
+        "std".to_string()
+    };
+    let (alphabet, pad, decode_padding) = match mode.as_str() {
+        "std" => (
+            &base64::alphabet::STANDARD,
+            true,
+            DecodePaddingMode::RequireCanonical,
+        ),
+        "std_nopad" => (
+            &base64::alphabet::STANDARD,
+            false,
+            DecodePaddingMode::Indifferent,
+        ),
+        "urlsafe" => (
+            &base64::alphabet::URL_SAFE,
+            true,
+            DecodePaddingMode::Indifferent,
+        ),
+        "urlsafe_nopad" => (
+            &base64::alphabet::URL_SAFE,
+            false,
+            DecodePaddingMode::Indifferent,
+        ),
+        _ => anyhow::bail!(
+            "unknown base64 mode '{}' (expected 'std', 'std_nopad', 'urlsafe' or 'urlsafe_nopad')",
+            mode
+        ),
+    };
+    let config = GeneralPurposeConfig::new()
+        .with_encode_padding(pad)
+        .with_decode_padding_mode(decode_padding);
+    Ok(GeneralPurpose::new(alphabet, config))
+}
+
+/// Returns true if any argument is NULL. Like most built-in SQLite functions,
+/// all of ours return NULL in that case instead of raising a type error.
+fn has_null_arg(ctx: &Context) -> bool {
+    (0..ctx.len()).any(|i| ctx.get_raw(i).data_type() == Type::Null)
+}
+
+fn null<'a>() -> ToSqlOutput<'a> {
+    ToSqlOutput::Owned(Value::Null)
+}
+
+fn blob_arg<'c>(ctx: &'c Context, idx: usize) -> anyhow::Result<&'c [u8]> {
+    match ctx.get_raw(idx) {
+        ValueRef::Blob(blob) => Ok(blob),
+        other => anyhow::bail!("expected BLOB argument, got {}", other.data_type()),
+    }
+}
+
+/// Returns a byte payload argument: a BLOB as is, or TEXT decoded as hex for
+/// when hex strings end up where raw bytes were expected.
+fn bytes_arg<'c>(ctx: &'c Context, idx: usize) -> anyhow::Result<std::borrow::Cow<'c, [u8]>> {
+    match ctx.get_raw(idx) {
+        ValueRef::Blob(blob) => Ok(blob.into()),
+        ValueRef::Text(text) => Ok(hex::decode(text)?.into()),
+        other => anyhow::bail!(
+            "expected BLOB or hex TEXT argument, got {}",
+            other.data_type()
+        ),
+    }
+}
+
+/// Returns the bytes of a BLOB or the UTF-8 bytes of a TEXT argument, for
+/// hash functions that take either.
+fn blob_or_text_arg<'c>(ctx: &'c Context, idx: usize) -> anyhow::Result<&'c [u8]> {
+    match ctx.get_raw(idx) {
+        ValueRef::Blob(bytes) | ValueRef::Text(bytes) => Ok(bytes),
+        other => anyhow::bail!("expected BLOB or TEXT argument, got {}", other.data_type()),
+    }
+}
+
+fn text_arg<'c>(ctx: &'c Context, idx: usize) -> anyhow::Result<&'c str> {
+    match ctx.get_raw(idx) {
+        ValueRef::Text(text) => Ok(std::str::from_utf8(text)?),
+        other => anyhow::bail!("expected TEXT argument, got {}", other.data_type()),
+    }
+}
+
+fn base64_encode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use base64::Engine;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let encoded = base64_engine(ctx, 1)?.encode(blob_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Text(encoded)))
+}
+
+fn base64_decode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use base64::{DecodeError, Engine};
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = base64_engine(ctx, 1)?
+        .decode(text_arg(ctx, 0)?)
+        .map_err(|e| match e {
+            DecodeError::InvalidByte(offset, byte)
+            | DecodeError::InvalidLastSymbol(offset, byte) => {
+                anyhow::anyhow!(
+                    "invalid base64 character {:?} at offset {}",
+                    byte as char,
+                    offset
+                )
+            }
+            e => anyhow::anyhow!("invalid base64: {}", e),
+        })?;
+    Ok(ToSqlOutput::Owned(Value::Blob(data)))
+}
+
+fn base58_encode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    Ok(ToSqlOutput::Owned(Value::Text(
+        bs58::encode(blob_arg(ctx, 0)?).into_string(),
+    )))
+}
+
+fn base58_decode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(base58_decode(text_arg(
+        ctx, 0,
+    )?)?)))
+}
+
+fn base58_decode(encoded: &str) -> anyhow::Result<Vec<u8>> {
+    bs58::decode(encoded)
+        .into_vec()
+        .map_err(|e| anyhow::anyhow!("invalid base58: {}", e))
+}
+
+/// Base58-encodes a payload with a 4-byte double-SHA256 checksum appended.
+fn base58check_encode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = blob_arg(ctx, 0)?;
+    let mut payload = data.to_vec();
+    payload.extend_from_slice(&double_sha256(data)[..4]);
+    Ok(ToSqlOutput::Owned(Value::Text(
+        bs58::encode(payload).into_string(),
+    )))
+}
+
+/// Decodes a base58check string, verifying and stripping the checksum. The
+/// returned blob includes the version byte(s), if any.
+fn base58check_decode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut payload = base58_decode(text_arg(ctx, 0)?)?;
+    if payload.len() < 4 {
+        anyhow::bail!(
+            "base58check payload too short: {} bytes, need at least 4",
+            payload.len()
+        );
+    }
+    let checksum = payload.split_off(payload.len() - 4);
+    if checksum[..] != double_sha256(&payload)[..4] {
+        anyhow::bail!("base58check checksum mismatch");
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(payload)))
+}
+
+fn parse_bech32_variant(name: &str) -> anyhow::Result<bech32::Variant> {
+    match name {
+        "bech32" => Ok(bech32::Variant::Bech32),
+        "bech32m" => Ok(bech32::Variant::Bech32m),
+        _ => anyhow::bail!(
+            "unknown bech32 variant '{}' (expected 'bech32' or 'bech32m')",
+            name
+        ),
+    }
+}
+
+fn bech32_variant_name(variant: bech32::Variant) -> &'static str {
+    match variant {
+        bech32::Variant::Bech32 => "bech32",
+        bech32::Variant::Bech32m => "bech32m",
+    }
+}
+
+/// Checks a human-readable part against the BIP-173 rules, so callers get a
+/// clear message instead of the bech32 crate's generic errors.
+fn validate_hrp(hrp: &str) -> anyhow::Result<()> {
+    let reason = if hrp.is_empty() {
+        "must not be empty"
+    } else if hrp.len() > 83 {
+        "must be at most 83 characters long"
+    } else if hrp.bytes().any(|c| !(33..=126).contains(&c)) {
+        "characters must be printable ASCII (33-126)"
+    } else if hrp.bytes().any(|c| c.is_ascii_lowercase())
+        && hrp.bytes().any(|c| c.is_ascii_uppercase())
+    {
+        "must not be mixed case"
+    } else {
+        return Ok(());
+    };
+    anyhow::bail!("invalid bech32 HRP '{}': {}", hrp, reason)
+}
+
+/// Number of recent results `bech32m_encode` caches per connection unless
+/// changed with `bech32m_encode_cache_size`. Queries tend to encode the same
+/// few puzzle hashes over and over.
+const BECH32M_ENCODE_CACHE_SIZE: usize = 4096;
+
+/// Largest cache size `bech32m_encode_cache_size` accepts. The cache's table
+/// is allocated up front, so this keeps a typo from exhausting memory.
+const BECH32M_ENCODE_CACHE_MAX_SIZE: usize = 1 << 20;
+
+type Bech32Cache = lru::LruCache<Vec<u8>, String>;
+
+/// The results `bech32m_encode` caches for a connection, along with a buffer
+/// cache keys are assembled in, so that lookups don't allocate.
+struct Bech32mEncodeCache {
+    size: usize,
+    results: Option<Bech32Cache>,
+    key: Vec<u8>,
+}
+
+impl Bech32mEncodeCache {
+    fn new(size: usize) -> Self {
+        Bech32mEncodeCache {
+            size,
+            results: std::num::NonZeroUsize::new(size).map(Bech32Cache::new),
+            key: Vec::new(),
+        }
+    }
+}
+
+/// Registers `bech32m_encode` with an LRU cache of up to `cache_size` results
+/// (0 disables caching), along with `bech32m_encode_cache_size` to resize it.
+/// The closures share the cache, so it is local to the connection and the
+/// function stays deterministic.
+fn create_bech32m_encode(
+    db: &rusqlite::Connection,
+    flags: FunctionFlags,
+    cache_size: usize,
+) -> anyhow::Result<()> {
+    let cache = std::sync::Arc::new(std::sync::Mutex::new(Bech32mEncodeCache::new(cache_size)));
+    // A panic while holding the lock leaves the cache consistent, since
+    // results are only inserted once fully encoded.
+    fn lock(
+        cache: &std::sync::Mutex<Bech32mEncodeCache>,
+    ) -> std::sync::MutexGuard<'_, Bech32mEncodeCache> {
+        cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    for n_arg in 1..=3 {
+        let cache = cache.clone();
+        db.create_scalar_function("bech32m_encode", n_arg, flags, move |ctx| {
+            bech32m_encode_cached(ctx, Some(&mut *lock(&cache))).map_err(ah)
+        })?;
+    }
+    // Changes connection state, so neither innocuous nor deterministic.
+    for n_arg in 0..=1 {
+        let cache = cache.clone();
+        db.create_scalar_function(
+            "bech32m_encode_cache_size",
+            n_arg,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DIRECTONLY,
+            move |ctx| bech32m_encode_cache_size(ctx, &mut *lock(&cache)).map_err(ah),
+        )?;
+    }
     Ok(())
 }
 
+/// Returns how many results `bech32m_encode` caches, after resizing (and
+/// emptying) the cache if a new size is given.
+fn bech32m_encode_cache_size<'a>(
+    ctx: &Context,
+    cache: &mut Bech32mEncodeCache,
+) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    if ctx.len() == 1 {
+        let size = u64_arg(ctx, 0)?;
+        if size > BECH32M_ENCODE_CACHE_MAX_SIZE as u64 {
+            anyhow::bail!(
+                "cache size must be at most {}, got {}",
+                BECH32M_ENCODE_CACHE_MAX_SIZE,
+                size
+            );
+        }
+        *cache = Bech32mEncodeCache::new(size as usize);
+    }
+    Ok(ToSqlOutput::Owned(Value::Integer(cache.size as i64)))
+}
+
 fn bech32m_encode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    use bech32::ToBase32;
-    let hrp = ctx.get::<String>(0)?;
-    let data = ctx.get::<Vec<u8>>(1)?;
-    let encoded = bech32::encode(&hrp, data.to_base32(), bech32::Variant::Bech32m)?;
+    bech32m_encode_cached(ctx, None)
+}
+
+/// Encodes data with the given HRP, or 'xch' if only the data is given. The
+/// optional third argument selects the checksum variant, 'bech32m' (default)
+/// or 'bech32'. Cache hits skip the base32 conversion and checksum, but still
+/// copy the result, as SQLite takes ownership of it.
+fn bech32m_encode_cached<'a>(
+    ctx: &Context,
+    cache: Option<&mut Bech32mEncodeCache>,
+) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let (hrp, data) = match ctx.len() {
+        1 => ("xch", bytes_arg(ctx, 0)?),
+        _ => {
+            let hrp = text_arg(ctx, 0)?;
+            validate_hrp(hrp)?;
+            (hrp, bytes_arg(ctx, 1)?)
+        }
+    };
+    let variant = match ctx.len() {
+        1 | 2 => bech32::Variant::Bech32m,
+        _ => parse_bech32_variant(text_arg(ctx, 2)?)?,
+    };
+    let encoded = match cache {
+        Some(Bech32mEncodeCache {
+            results: Some(results),
+            key,
+            ..
+        }) => {
+            // Valid HRPs are at most 83 bytes long, so the length prefix keeps
+            // keys unambiguous.
+            key.clear();
+            key.push(variant as u8);
+            key.push(hrp.len() as u8);
+            key.extend_from_slice(hrp.as_bytes());
+            key.extend_from_slice(&data);
+            match results.get(&*key).cloned() {
+                Some(encoded) => encoded,
+                None => {
+                    let encoded = bech32::encode(hrp, &data, variant)?;
+                    results.put(key.clone(), encoded.clone());
+                    encoded
+                }
+            }
+        }
+        _ => bech32::encode(hrp, &data, variant)?,
+    };
+    Ok(ToSqlOutput::Owned(Value::Text(encoded)))
+}
+
+/// Like `bech32m_encode`, with the original bech32 checksum.
+fn bech32_encode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let hrp = text_arg(ctx, 0)?;
+    validate_hrp(hrp)?;
+    let encoded = bech32::encode(hrp, &bytes_arg(ctx, 1)?, bech32::Variant::Bech32)?;
     Ok(ToSqlOutput::Owned(Value::Text(encoded)))
 }
 
+/// Decodes a bech32m string into a blob. The optional further arguments are
+/// the HRP the string must have (TEXT) and the number of bytes it must decode
+/// to (INTEGER). Strings with a plain bech32 checksum are rejected since they
+/// are not valid Chia addresses.
 fn bech32m_decode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    use bech32::FromBase32;
-    let encoded = ctx.get::<String>(0)?;
-    let (_hrp, data, _variant) = bech32::decode(&encoded)?;
-    Ok(ToSqlOutput::Owned(Value::Blob(Vec::<u8>::from_base32(
-        &data,
-    )?)))
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = bech32_decode_args(ctx, Some(bech32::Variant::Bech32m))?;
+    Ok(ToSqlOutput::Owned(Value::Blob(data)))
 }
 
-fn blob_from_hex_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    let hex = ctx.get::<String>(0)?;
-    let data = (0..hex.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
-        .collect::<Result<Vec<u8>, std::num::ParseIntError>>()?;
+/// Like `bech32m_decode`, but accepts either checksum variant.
+fn bech32_decode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = bech32_decode_args(ctx, None)?;
     Ok(ToSqlOutput::Owned(Value::Blob(data)))
 }
 
-fn chia_amount_int<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    let blob = ctx.get::<Vec<u8>>(0)?;
-    let bytes: [u8; 8] = blob.try_into().unwrap();
-    let mojos = i64::from_be_bytes(bytes); // @@ i64 != u64
-    Ok(ToSqlOutput::Owned(Value::Integer(mojos)))
+/// Decodes the string in the first argument and checks its checksum against
+/// `variant`, if given, and its HRP and length against the optional further
+/// arguments.
+fn bech32_decode_args(ctx: &Context, variant: Option<bech32::Variant>) -> anyhow::Result<Vec<u8>> {
+    let (hrp, data, found) = bech32::decode(text_arg(ctx, 0)?)?;
+    if let Some(expected) = variant {
+        if found != expected {
+            anyhow::bail!(
+                "expected {} checksum, found {}",
+                bech32_variant_name(expected),
+                bech32_variant_name(found)
+            );
+        }
+    }
+    for i in 1..ctx.len() {
+        match ctx.get_raw(i) {
+            ValueRef::Text(_) => {
+                let expected = text_arg(ctx, i)?.to_ascii_lowercase();
+                if hrp != expected {
+                    anyhow::bail!("expected HRP '{}', got '{}'", expected, hrp);
+                }
+            }
+            ValueRef::Integer(expected) => {
+                if i64::try_from(data.len()).map_or(true, |len| len != expected) {
+                    anyhow::bail!(
+                        "expected {} decoded bytes, got {} bytes",
+                        expected,
+                        data.len()
+                    );
+                }
+            }
+            other => anyhow::bail!(
+                "expected TEXT HRP or INTEGER length argument, got {}",
+                other.data_type()
+            ),
+        }
+    }
+    Ok(data)
 }
 
-fn chia_fullblock_json<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    use chia_traits::streamable::Streamable;
-    let blob = ctx.get::<Vec<u8>>(0)?;
-    let block = chia_protocol::FullBlock::parse::<true>(&mut Cursor::new(&blob))?;
-    let json: String = serde_json::to_string(&block)?;
-    Ok(ToSqlOutput::Owned(Value::Text(json)))
+/// Returns the (lowercase) human-readable part of a bech32m string.
+fn bech32m_hrp_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let (hrp, _data, _variant) = bech32::decode(text_arg(ctx, 0)?)?;
+    Ok(ToSqlOutput::Owned(Value::Text(hrp)))
 }
 
-fn sha256sum<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    use sha2::Digest;
-    let blob = ctx.get::<Vec<u8>>(0)?;
-    let data = blob.as_slice();
-    let digest = sha2::Sha256::digest(data);
-    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+/// Like `bech32m_decode`, additionally checking that the address holds a
+/// 32-byte puzzle hash.
+fn address_to_puzzle_hash<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = bech32_decode_args(ctx, Some(bech32::Variant::Bech32m))?;
+    if data.len() != 32 {
+        anyhow::bail!(
+            "address must decode to a 32-byte puzzle hash, got {} bytes",
+            data.len()
+        );
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(data)))
 }
 
-fn zstd_decompress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
-    let blob = ctx.get::<Vec<u8>>(0)?;
-    let out = zstd::stream::decode_all(blob.as_slice())?;
-    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+/// Returns 1 if the argument is a bech32m string holding a 32-byte puzzle
+/// hash, with HRP 'xch' or 'txch' unless the optional second argument names
+/// another one. Never raises an error: anything else, including arguments that
+/// aren't TEXT, yields 0.
+fn chia_address_valid<'a>(ctx: &Context) -> ToSqlOutput<'a> {
+    if has_null_arg(ctx) {
+        return null();
+    }
+    let valid = match address_hrp(ctx, 0) {
+        Some(hrp) if ctx.len() > 1 => {
+            text_arg(ctx, 1).map_or(false, |expected| hrp.eq_ignore_ascii_case(expected))
+        }
+        Some(hrp) => hrp == "xch" || hrp == "txch",
+        None => false,
+    };
+    ToSqlOutput::Owned(Value::Integer(valid.into()))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::Context;
-    use pretty_assertions::assert_eq;
+/// Returns 'mainnet' for 'xch' addresses, 'testnet' for 'txch' addresses and
+/// NULL for anything else, like `chia_address_valid` never raising an error.
+fn chia_address_network<'a>(ctx: &Context) -> ToSqlOutput<'a> {
+    let network = match address_hrp(ctx, 0).as_deref() {
+        Some("xch") => "mainnet",
+        Some("txch") => "testnet",
+        _ => return null(),
+    };
+    ToSqlOutput::Owned(Value::Text(network.to_string()))
+}
 
-    use rusqlite::Connection;
+/// Longest string `chia_bech32_kind` decodes, far above the size of real
+/// offers while keeping a runaway value from being decoded.
+const CHIA_BECH32_KIND_MAX_LENGTH: usize = 1 << 20;
 
-    fn open_db() -> anyhow::Result<Connection> {
-        let db = Connection::open_in_memory().context("open in-memory db")?;
-        setup(&db).context("setup db")?;
-        Ok(db)
+/// Classifies a pasted Chia string as 'address' (an 'xch' or 'txch' bech32m
+/// string holding 32 bytes), 'offer' (an 'offer' bech32m string, up to
+/// `CHIA_BECH32_KIND_MAX_LENGTH` characters long) or 'unknown', never raising
+/// an error.
+fn chia_bech32_kind<'a>(ctx: &Context) -> ToSqlOutput<'a> {
+    if has_null_arg(ctx) {
+        return null();
     }
+    let kind = match text_arg(ctx, 0).map(|s| bech32::decode_max(s, CHIA_BECH32_KIND_MAX_LENGTH)) {
+        Ok(Ok((hrp, data, bech32::Variant::Bech32m))) => match hrp.as_str() {
+            "xch" | "txch" if data.len() == 32 => "address",
+            "offer" if !data.is_empty() => "offer",
+            _ => "unknown",
+        },
+        _ => "unknown",
+    };
+    ToSqlOutput::Owned(Value::Text(kind.to_string()))
+}
 
-    #[test]
-    fn sanity() -> anyhow::Result<()> {
-        open_db()?;
-        Ok(())
+/// Returns the HRP of a TEXT argument if it's a bech32m string holding 32
+/// bytes, as addresses do.
+fn address_hrp(ctx: &Context, idx: usize) -> Option<String> {
+    let (hrp, data, variant) = bech32::decode(text_arg(ctx, idx).ok()?).ok()?;
+    if variant == bech32::Variant::Bech32m && data.len() == 32 {
+        Some(hrp)
+    } else {
+        None
     }
+}
 
-    #[test]
-    fn bech32m_encode_works() -> anyhow::Result<()> {
-        let db = open_db()?;
-        assert_eq!(
-            "xch1jlgazv".to_string(),
-            db.query_row("select bech32m_encode('xch', x'')", [], |r| r
-                .get::<usize, String>(0))?
-        );
-        assert_eq!(
-            "xch1etlqusgk05".to_string(),
-            db.query_row("select bech32m_encode('xch', x'cafe')", [], |r| r
-                .get::<usize, String>(0))?
+/// Encodes a 32-byte puzzle hash as address with the given HRP, the inverse
+/// of `address_to_puzzle_hash`.
+fn puzzle_hash_to_address<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let puzzle_hash = bytes_arg(ctx, 0)?;
+    if puzzle_hash.len() != 32 {
+        anyhow::bail!(
+            "puzzle hash must be 32 bytes long, got {} bytes",
+            puzzle_hash.len()
         );
-        assert_eq!("xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4".to_string(),
-                   db.query_row("select bech32m_encode('xch', x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669')",
-                                [],
-                                |r| r.get::<usize, String>(0))?);
-        Ok(())
     }
+    let hrp = text_arg(ctx, 1)?;
+    validate_hrp(hrp)?;
+    let address = bech32::encode(hrp, &puzzle_hash, bech32::Variant::Bech32m)?;
+    Ok(ToSqlOutput::Owned(Value::Text(address)))
+}
 
-    #[test]
-    fn bech32m_decode_works() -> anyhow::Result<()> {
-        let db = open_db()?;
-        assert_eq!(
-            "".to_string(),
-            db.query_row("select hex(bech32m_decode('xch1jlgazv'))", [], |r| r
-                .get::<usize, String>(0))?
-        );
-        assert_eq!(
-            "CAFE".to_string(),
-            db.query_row("select hex(bech32m_decode('xch1etlqusgk05'))", [], |r| {
-                r.get::<usize, String>(0)
-            })?
-        );
-        assert_eq!("F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669".to_string(),
-                   db.query_row("select hex(bech32m_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'))",
-                                [],
-                                |r| r.get::<usize, String>(0))?);
-        Ok(())
+/// Returns the checksum variant ('bech32' or 'bech32m') of an encoded string.
+fn bech32_variant_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
     }
+    let (_hrp, _data, variant) = bech32::decode(text_arg(ctx, 0)?)?;
+    Ok(ToSqlOutput::Owned(Value::Text(
+        bech32_variant_name(variant).to_string(),
+    )))
+}
 
-    #[test]
-    fn blob_from_hex_works() -> anyhow::Result<()> {
-        let db = open_db()?;
-        assert_eq!(
-            "CAFE".to_string(),
-            db.query_row("select hex(blob_from_hex('cafe'))", [], |r| r
-                .get::<usize, String>(0))?
-        );
-        Ok(())
+/// Computes the fingerprint wallets identify keys by: the first 4 bytes of the
+/// SHA256 digest of the public key, as big-endian integer. Like chia itself,
+/// this doesn't check that the key is a valid point.
+fn bls_fingerprint<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
     }
+    let pk = bytes_arg(ctx, 0)?;
+    if pk.len() != 48 {
+        anyhow::bail!("public key must be 48 bytes long, got {} bytes", pk.len());
+    }
+    let digest = sha2::Sha256::digest(&pk);
+    let fingerprint = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+    Ok(ToSqlOutput::Owned(Value::Integer(fingerprint.into())))
+}
 
-    #[test]
-    fn chia_amount_int_works() -> anyhow::Result<()> {
-        let db = open_db()?;
-        assert_eq!(
-            5509699999997u64,
-            db.query_row("select chia_amount_int(x'00000502D3B618FD')", [], |r| r
-                .get::<usize, u64>(
-                0
-            ))?
-        );
-        Ok(())
+/// Returns 1 if the argument is a valid compressed G1 point, i.e. a BLS public
+/// key, and 0 for anything else of the right type. The point at infinity
+/// counts as valid unless the optional second argument is false.
+#[cfg(feature = "bls")]
+fn bls_pubkey_valid<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
     }
+    let blob = blob_arg(ctx, 0)?;
+    let allow_infinity = ctx.len() < 2 || ctx.get::<bool>(1)?;
+    let valid = match <&[u8; 48]>::try_from(blob) {
+        Ok(bytes) => {
+            chia_bls::PublicKey::from_bytes(bytes).is_ok()
+                && (allow_infinity || !is_bls_infinity(bytes))
+        }
+        Err(_) => false,
+    };
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
 
-    #[test]
-    fn sha256sum_works() -> anyhow::Result<()> {
+/// Like `bls_pubkey_valid`, for compressed G2 points, i.e. BLS signatures.
+#[cfg(feature = "bls")]
+fn bls_signature_valid<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = blob_arg(ctx, 0)?;
+    let allow_infinity = ctx.len() < 2 || ctx.get::<bool>(1)?;
+    let valid = match <&[u8; 96]>::try_from(blob) {
+        Ok(bytes) => {
+            chia_bls::Signature::from_bytes(bytes).is_ok()
+                && (allow_infinity || !is_bls_infinity(bytes))
+        }
+        Err(_) => false,
+    };
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
+
+/// Verifies a signature under the augmented scheme chia uses for `AGG_SIG_*`
+/// conditions, returning 1 or 0. Invalid public keys or signatures yield 0.
+#[cfg(feature = "bls")]
+fn bls_verify<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = <&[u8; 48]>::try_from(blob_arg(ctx, 0)?)
+        .ok()
+        .and_then(|bytes| chia_bls::PublicKey::from_bytes(bytes).ok());
+    let message = blob_or_text_arg(ctx, 1)?;
+    let sig = <&[u8; 96]>::try_from(blob_arg(ctx, 2)?)
+        .ok()
+        .and_then(|bytes| chia_bls::Signature::from_bytes(bytes).ok());
+    let valid = match (pk, sig) {
+        (Some(pk), Some(sig)) => chia_bls::verify(&sig, &pk, message),
+        _ => false,
+    };
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
+
+#[cfg(feature = "secp")]
+type SecpVerify = fn(&[u8; 33], &[u8; 32], &[u8; 64]) -> bool;
+
+/// Verifies an ECDSA signature like CLVM's `secp256k1_verify` and
+/// `secp256r1_verify` operators, taking a compressed 33-byte public key, a
+/// 32-byte message digest and a 64-byte `r || s` signature. Returns 1 or 0;
+/// malformed arguments yield 0.
+#[cfg(feature = "secp")]
+fn secp_verify<'a>(ctx: &Context, verify: SecpVerify) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = <&[u8; 33]>::try_from(blob_arg(ctx, 0)?);
+    let digest = <&[u8; 32]>::try_from(blob_arg(ctx, 1)?);
+    let sig = <&[u8; 64]>::try_from(blob_arg(ctx, 2)?);
+    let valid = match (pk, digest, sig) {
+        (Ok(pk), Ok(digest), Ok(sig)) => verify(pk, digest, sig),
+        _ => false,
+    };
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
+
+/// Like the `k256` crate CLVM uses, this rejects signatures with a high `s`.
+#[cfg(feature = "secp")]
+fn secp256k1_verify(pk: &[u8; 33], digest: &[u8; 32], sig: &[u8; 64]) -> bool {
+    use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+    match (
+        VerifyingKey::from_sec1_bytes(pk),
+        Signature::from_slice(sig),
+    ) {
+        (Ok(pk), Ok(sig)) => pk.verify_prehash(digest, &sig).is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "secp")]
+fn secp256r1_verify(pk: &[u8; 33], digest: &[u8; 32], sig: &[u8; 64]) -> bool {
+    use p256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+    match (
+        VerifyingKey::from_sec1_bytes(pk),
+        Signature::from_slice(sig),
+    ) {
+        (Ok(pk), Ok(sig)) => pk.verify_prehash(digest, &sig).is_ok(),
+        _ => false,
+    }
+}
+
+/// Aggregate summing BLS public keys. NULL values are skipped; an empty group
+/// yields the point at infinity.
+#[cfg(feature = "bls")]
+struct BlsAggregatePks;
+
+#[cfg(feature = "bls")]
+impl rusqlite::functions::Aggregate<(chia_bls::PublicKey, usize), Vec<u8>> for BlsAggregatePks {
+    fn init(&self) -> (chia_bls::PublicKey, usize) {
+        Default::default()
+    }
+
+    fn step(
+        &self,
+        ctx: &mut Context<'_>,
+        (sum, row): &mut (chia_bls::PublicKey, usize),
+    ) -> rusqlite::Result<()> {
+        *row += 1;
+        if ctx.get_raw(0).data_type() == Type::Null {
+            return Ok(());
+        }
+        let pk = <&[u8; 48]>::try_from(blob_arg(ctx, 0).map_err(ah)?)
+            .ok()
+            .and_then(|bytes| chia_bls::PublicKey::from_bytes(bytes).ok())
+            .ok_or_else(|| ah(anyhow::anyhow!("invalid BLS public key in row {}", row)))?;
+        *sum += &pk;
+        Ok(())
+    }
+
+    fn finalize(&self, state: Option<(chia_bls::PublicKey, usize)>) -> rusqlite::Result<Vec<u8>> {
+        Ok(state.unwrap_or_default().0.to_bytes().to_vec())
+    }
+}
+
+/// Aggregate combining BLS signatures, like `BlsAggregatePks`.
+#[cfg(feature = "bls")]
+struct BlsAggregateSigs;
+
+#[cfg(feature = "bls")]
+impl rusqlite::functions::Aggregate<(chia_bls::Signature, usize), Vec<u8>> for BlsAggregateSigs {
+    fn init(&self) -> (chia_bls::Signature, usize) {
+        Default::default()
+    }
+
+    fn step(
+        &self,
+        ctx: &mut Context<'_>,
+        (sum, row): &mut (chia_bls::Signature, usize),
+    ) -> rusqlite::Result<()> {
+        *row += 1;
+        if ctx.get_raw(0).data_type() == Type::Null {
+            return Ok(());
+        }
+        let sig = <&[u8; 96]>::try_from(blob_arg(ctx, 0).map_err(ah)?)
+            .ok()
+            .and_then(|bytes| chia_bls::Signature::from_bytes(bytes).ok())
+            .ok_or_else(|| ah(anyhow::anyhow!("invalid BLS signature in row {}", row)))?;
+        *sum += &sig;
+        Ok(())
+    }
+
+    fn finalize(&self, state: Option<(chia_bls::Signature, usize)>) -> rusqlite::Result<Vec<u8>> {
+        Ok(state.unwrap_or_default().0.to_bytes().to_vec())
+    }
+}
+
+/// Verifies an aggregate signature of the messages in a JSON array of
+/// `{"pk": ..., "msg": ...}` objects (both hex strings), as `bls_verify` does
+/// for single signatures. Returns 0 for invalid keys or signatures, but raises
+/// an error for malformed JSON.
+#[cfg(feature = "bls")]
+fn bls_aggregate_verify<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use anyhow::Context as _;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let json: serde_json::Value = serde_json::from_str(text_arg(ctx, 0)?)?;
+    let pairs = json
+        .as_array()
+        .context("expected a JSON array of {\"pk\": ..., \"msg\": ...} objects")?;
+    let mut data = Vec::with_capacity(pairs.len());
+    for (i, pair) in pairs.iter().enumerate() {
+        let field = |name: &str| -> anyhow::Result<Vec<u8>> {
+            let digits = pair[name]
+                .as_str()
+                .with_context(|| format!("pair {} has no \"{}\" string", i + 1, name))?;
+            hex::decode(digits.as_bytes())
+                .with_context(|| format!("invalid {} in pair {}", name, i + 1))
+        };
+        let msg = field("msg")?;
+        match <&[u8; 48]>::try_from(&field("pk")?[..])
+            .ok()
+            .and_then(|bytes| chia_bls::PublicKey::from_bytes(bytes).ok())
+        {
+            Some(pk) => data.push((pk, msg)),
+            None => return Ok(ToSqlOutput::Owned(Value::Integer(0))),
+        }
+    }
+    let valid = match <&[u8; 96]>::try_from(blob_arg(ctx, 1)?)
+        .ok()
+        .and_then(|bytes| chia_bls::Signature::from_bytes(bytes).ok())
+    {
+        Some(sig) => chia_bls::aggregate_verify(&sig, data.iter().map(|(pk, msg)| (pk, msg))),
+        None => false,
+    };
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
+
+/// Derives the child secret key at an index per EIP-2333, or with chia's
+/// unhardened scheme, which lets public keys be derived alike.
+#[cfg(feature = "bls")]
+fn bls_derive_child_sk<'a>(ctx: &Context, hardened: bool) -> anyhow::Result<ToSqlOutput<'a>> {
+    use chia_bls::derivable_key::DerivableKey;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let sk = secret_key_arg(ctx, 0)?;
+    let index = derivation_index_arg(ctx, 1)?;
+    let child = if hardened {
+        sk.derive_hardened(index)
+    } else {
+        sk.derive_unhardened(index)
+    };
+    Ok(ToSqlOutput::Owned(Value::Blob(child.to_bytes().to_vec())))
+}
+
+/// Signs a message under the augmented scheme, the counterpart of
+/// `bls_verify`.
+#[cfg(feature = "bls")]
+fn bls_sign<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let sk = secret_key_arg(ctx, 0)?;
+    let sig = chia_bls::sign(&sk, blob_or_text_arg(ctx, 1)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(sig.to_bytes().to_vec())))
+}
+
+/// Returns the public key of a secret key.
+#[cfg(feature = "bls")]
+fn bls_sk_to_pk<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = secret_key_arg(ctx, 0)?.public_key();
+    Ok(ToSqlOutput::Owned(Value::Blob(pk.to_bytes().to_vec())))
+}
+
+/// Turns a BIP-39 mnemonic into the seed keys are derived from, checking its
+/// words and checksum. The passphrase defaults to the empty one chia uses.
+#[cfg(feature = "bls")]
+fn chia_mnemonic_to_seed<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let words = text_arg(ctx, 0)?;
+    let passphrase = if ctx.len() > 1 { text_arg(ctx, 1)? } else { "" };
+    let mnemonic =
+        bip39::Mnemonic::parse_in(bip39::Language::English, words).map_err(|e| match e {
+            bip39::Error::UnknownWord(i) => anyhow::anyhow!(
+                "unknown mnemonic word '{}' at position {}",
+                words.split_whitespace().nth(i).unwrap_or_default(),
+                i + 1
+            ),
+            bip39::Error::BadWordCount(n) => {
+                anyhow::anyhow!("mnemonic must have 12, 15, 18, 21 or 24 words, got {}", n)
+            }
+            bip39::Error::InvalidChecksum => anyhow::anyhow!("invalid mnemonic checksum"),
+            e => anyhow::anyhow!("invalid mnemonic: {}", e),
+        })?;
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        mnemonic.to_seed(passphrase).to_vec(),
+    )))
+}
+
+/// Derives the master secret key from a seed per EIP-2333.
+#[cfg(feature = "bls")]
+fn chia_master_sk_from_seed<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let seed = bytes_arg(ctx, 0)?;
+    // The minimum EIP-2333 allows.
+    if seed.len() < 32 {
+        anyhow::bail!(
+            "seed must be at least 32 bytes long, got {} bytes",
+            seed.len()
+        );
+    }
+    let sk = chia_bls::SecretKey::from_seed(&seed);
+    Ok(ToSqlOutput::Owned(Value::Blob(sk.to_bytes().to_vec())))
+}
+
+/// Derives the public key of a wallet's `index`th address from the master
+/// secret key, at m/12381/8444/2/index. Like the chia wallet, this uses
+/// unhardened (observer) derivation unless the optional third argument is
+/// true.
+#[cfg(feature = "bls")]
+fn chia_wallet_pk<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use chia_bls::derivable_key::DerivableKey;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut sk = secret_key_arg(ctx, 0)?;
+    let index = derivation_index_arg(ctx, 1)?;
+    let hardened = ctx.len() > 2 && ctx.get::<bool>(2)?;
+    for &i in &[12381, 8444, 2, index] {
+        sk = if hardened {
+            sk.derive_hardened(i)
+        } else {
+            sk.derive_unhardened(i)
+        };
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        sk.public_key().to_bytes().to_vec(),
+    )))
+}
+
+/// Derives the child public key at an index with chia's unhardened scheme,
+/// the public key of `bls_derive_child_sk_unhardened`'s result.
+#[cfg(feature = "bls")]
+fn bls_derive_child_pk_unhardened<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use chia_bls::derivable_key::DerivableKey;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = public_key_arg(ctx, 0)?;
+    let index = derivation_index_arg(ctx, 1)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        pk.derive_unhardened(index).to_bytes().to_vec(),
+    )))
+}
+
+/// Like `chia_wallet_pk`, deriving the observer key of an address from the
+/// master public key alone.
+#[cfg(feature = "bls")]
+fn chia_observer_pk<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use chia_bls::derivable_key::DerivableKey;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut pk = public_key_arg(ctx, 0)?;
+    let index = derivation_index_arg(ctx, 1)?;
+    for &i in &[12381, 8444, 2, index] {
+        pk = pk.derive_unhardened(i);
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(pk.to_bytes().to_vec())))
+}
+
+/// The hidden puzzle used by the standard wallet, `(=)`, which can never be
+/// satisfied.
+#[cfg(feature = "bls")]
+const DEFAULT_HIDDEN_PUZZLE_HASH: [u8; 32] = [
+    0x71, 0x1d, 0x6c, 0x4e, 0x32, 0xc9, 0x2e, 0x53, 0x17, 0x9b, 0x19, 0x94, 0x84, 0xcf, 0x8c, 0x89,
+    0x75, 0x42, 0xbc, 0x57, 0xf2, 0xb2, 0x25, 0x82, 0x79, 0x9f, 0x9d, 0x65, 0x7e, 0xec, 0x46, 0x99,
+];
+
+/// The order of the BLS12-381 groups, big-endian.
+#[cfg(feature = "bls")]
+const BLS_GROUP_ORDER: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// Computes the synthetic public key the standard wallet puzzle
+/// (`p2_delegated_puzzle_or_hidden_puzzle`) is curried with: the public key
+/// plus the point of `sha256(public_key || hidden_puzzle_hash)`.
+#[cfg(feature = "bls")]
+fn chia_synthetic_pk<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = public_key_arg(ctx, 0)?;
+    let hidden_puzzle_hash = if ctx.len() > 1 {
+        let hash = bytes_arg(ctx, 1)?;
+        <[u8; 32]>::try_from(&hash[..]).map_err(|_| {
+            anyhow::anyhow!(
+                "hidden puzzle hash must be 32 bytes long, got {} bytes",
+                hash.len()
+            )
+        })?
+    } else {
+        DEFAULT_HIDDEN_PUZZLE_HASH
+    };
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(pk.to_bytes());
+    hasher.update(hidden_puzzle_hash);
+    let offset = synthetic_offset(&hasher.finalize().into());
+    let mut synthetic = pk;
+    // A zero offset is no valid secret key, and leaves the key as is anyway.
+    if offset != [0; 32] {
+        let offset = chia_bls::SecretKey::from_bytes(&offset)
+            .map_err(|_| anyhow::anyhow!("invalid synthetic key offset"))?;
+        synthetic += &offset.public_key();
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        synthetic.to_bytes().to_vec(),
+    )))
+}
+
+/// The tree hash of `p2_delegated_puzzle_or_hidden_puzzle`, the standard
+/// wallet puzzle before currying in the synthetic public key.
+#[cfg(feature = "bls")]
+const STANDARD_PUZZLE_HASH: [u8; 32] = [
+    0xe9, 0xaa, 0xa4, 0x9f, 0x45, 0xba, 0xd5, 0xc8, 0x89, 0xb8, 0x6e, 0xe3, 0x34, 0x15, 0x50, 0xc1,
+    0x55, 0xcf, 0xdd, 0x10, 0xc3, 0xa6, 0x75, 0x7d, 0xe6, 0x18, 0xd2, 0x06, 0x12, 0xff, 0xfd, 0x52,
+];
+
+/// Computes the puzzle hash of the standard wallet puzzle for a synthetic
+/// public key.
+#[cfg(feature = "bls")]
+fn chia_puzzle_hash_for_pk<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = public_key_arg(ctx, 0)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        standard_puzzle_hash(&pk).to_vec(),
+    )))
+}
+
+/// Like `chia_puzzle_hash_for_pk`, returning the puzzle hash as address with
+/// the given HRP.
+#[cfg(feature = "bls")]
+fn chia_address_for_pk<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let pk = public_key_arg(ctx, 0)?;
+    let hrp = text_arg(ctx, 1)?;
+    validate_hrp(hrp)?;
+    let address = bech32::encode(hrp, &standard_puzzle_hash(&pk), bech32::Variant::Bech32m)?;
+    Ok(ToSqlOutput::Owned(Value::Text(address)))
+}
+
+#[cfg(feature = "bls")]
+fn standard_puzzle_hash(synthetic_pk: &chia_bls::PublicKey) -> [u8; 32] {
+    let pk_hash = clvm::hash_atom(&synthetic_pk.to_bytes());
+    clvm::curry_tree_hash(&STANDARD_PUZZLE_HASH, &[pk_hash])
+}
+
+/// Reduces a digest, read as signed big-endian integer like chia does, modulo
+/// the group order.
+#[cfg(feature = "bls")]
+fn synthetic_offset(digest: &[u8; 32]) -> [u8; 32] {
+    if digest[0] & 0x80 == 0 {
+        // Below 2^255, which is less than twice the order.
+        match sub_be(digest, &BLS_GROUP_ORDER) {
+            (diff, false) => diff,
+            (_, true) => *digest,
+        }
+    } else {
+        // At least -2^255 (as wrapped by adding 2^256), so adding the order
+        // once or twice makes it non-negative.
+        match add_be(digest, &BLS_GROUP_ORDER) {
+            (sum, true) => sum,
+            (sum, false) => add_be(&sum, &BLS_GROUP_ORDER).0,
+        }
+    }
+}
+
+/// Adds two 256-bit big-endian numbers, returning the carry.
+#[cfg(feature = "bls")]
+fn add_be(a: &[u8; 32], b: &[u8; 32]) -> ([u8; 32], bool) {
+    let mut out = [0; 32];
+    let mut carry = 0;
+    for i in (0..32).rev() {
+        let sum = u16::from(a[i]) + u16::from(b[i]) + carry;
+        out[i] = sum as u8;
+        carry = sum >> 8;
+    }
+    (out, carry != 0)
+}
+
+/// Subtracts two 256-bit big-endian numbers, returning the borrow.
+#[cfg(feature = "bls")]
+fn sub_be(a: &[u8; 32], b: &[u8; 32]) -> ([u8; 32], bool) {
+    let mut out = [0; 32];
+    let mut borrow = 0;
+    for i in (0..32).rev() {
+        let diff = i16::from(a[i]) - i16::from(b[i]) - borrow;
+        out[i] = diff as u8;
+        borrow = i16::from(diff < 0);
+    }
+    (out, borrow != 0)
+}
+
+#[cfg(feature = "bls")]
+fn public_key_arg(ctx: &Context, idx: usize) -> anyhow::Result<chia_bls::PublicKey> {
+    let bytes = bytes_arg(ctx, idx)?;
+    let bytes = <&[u8; 48]>::try_from(&bytes[..]).map_err(|_| {
+        anyhow::anyhow!(
+            "public key must be 48 bytes long, got {} bytes",
+            bytes.len()
+        )
+    })?;
+    chia_bls::PublicKey::from_bytes(bytes)
+        .map_err(|_| anyhow::anyhow!("public key is not a valid BLS12-381 G1 point"))
+}
+
+#[cfg(feature = "bls")]
+fn secret_key_arg(ctx: &Context, idx: usize) -> anyhow::Result<chia_bls::SecretKey> {
+    let bytes = bytes_arg(ctx, idx)?;
+    if bytes.len() == 48 {
+        anyhow::bail!(
+            "secret key must be 32 bytes long, got a 48-byte public key \
+             (hardened keys can only be derived from secret keys)"
+        );
+    }
+    let bytes = <&[u8; 32]>::try_from(&bytes[..]).map_err(|_| {
+        anyhow::anyhow!(
+            "secret key must be 32 bytes long, got {} bytes",
+            bytes.len()
+        )
+    })?;
+    chia_bls::SecretKey::from_bytes(bytes)
+        .map_err(|_| anyhow::anyhow!("secret key is not a valid BLS12-381 scalar"))
+}
+
+#[cfg(feature = "bls")]
+fn derivation_index_arg(ctx: &Context, idx: usize) -> anyhow::Result<u32> {
+    let n = ctx.get::<i64>(idx)?;
+    u32::try_from(n).map_err(|_| {
+        anyhow::anyhow!(
+            "derivation index must be between 0 and {}, got {}",
+            u32::MAX,
+            n
+        )
+    })
+}
+
+/// Returns true for the (only valid) encoding of the point at infinity, the
+/// compression and infinity flags followed by zeros.
+#[cfg(feature = "bls")]
+fn is_bls_infinity(point: &[u8]) -> bool {
+    point[0] == 0xc0 && point[1..].iter().all(|&b| b == 0)
+}
+
+/// Combines two blobs byte by byte. They must have the same length, unless the
+/// optional third argument is true, in which case the shorter one is
+/// zero-extended on the left (as for big-endian numbers).
+fn blob_bitwise_fn<'a>(ctx: &Context, op: fn(u8, u8) -> u8) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let a = blob_arg(ctx, 0)?;
+    let b = blob_arg(ctx, 1)?;
+    let zero_extend = ctx.len() > 2 && ctx.get::<bool>(2)?;
+    if a.len() != b.len() && !zero_extend {
+        anyhow::bail!("blob lengths differ ({} and {} bytes)", a.len(), b.len());
+    }
+    let len = a.len().max(b.len());
+    let byte = |blob: &[u8], i: usize| (i + blob.len()).checked_sub(len).map_or(0, |j| blob[j]);
+    let out = (0..len).map(|i| op(byte(a, i), byte(b, i))).collect();
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+fn blob_not_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let out = blob_arg(ctx, 0)?.iter().map(|b| !b).collect();
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+fn blob_concat_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut out = Vec::new();
+    for i in 0..ctx.len() {
+        out.extend_from_slice(blob_arg(ctx, i)?);
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+fn blob_reverse_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut out = blob_arg(ctx, 0)?.to_vec();
+    out.reverse();
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+/// Returns `len` bytes (or all remaining ones if omitted) starting at the
+/// 0-based offset `start`. Like in Python, a negative `start` counts from the
+/// end, and out-of-range offsets are clamped rather than raising an error.
+fn blob_slice_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = blob_arg(ctx, 0)?;
+    let start = ctx.get::<i64>(1)?;
+    let start = if start < 0 {
+        blob.len().saturating_sub(start.unsigned_abs() as usize)
+    } else {
+        usize::try_from(start).unwrap_or(usize::MAX).min(blob.len())
+    };
+    let end = if ctx.len() > 2 {
+        let len = ctx.get::<i64>(2)?;
+        if len < 0 {
+            anyhow::bail!("slice length must not be negative, got {}", len);
+        }
+        start
+            .saturating_add(usize::try_from(len).unwrap_or(usize::MAX))
+            .min(blob.len())
+    } else {
+        blob.len()
+    };
+    Ok(ToSqlOutput::Owned(Value::Blob(blob[start..end].to_vec())))
+}
+
+fn blob_from_hex_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    // Hex digits stored as a BLOB are decoded all the same.
+    let hex = match ctx.get_raw(0) {
+        ValueRef::Text(hex) | ValueRef::Blob(hex) => hex,
+        other => anyhow::bail!("expected TEXT argument, got {}", other.data_type()),
+    };
+    Ok(ToSqlOutput::Owned(Value::Blob(hex::decode(hex)?)))
+}
+
+/// Hex-encodes a blob. The optional second argument selects the output style:
+/// 'lower' (default), 'upper', or '0x' for lowercase with a '0x' prefix.
+fn blob_to_hex_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = blob_arg(ctx, 0)?;
+    // Besides a style name, a boolean (i.e. integer) flag selects whether to
+    // add the '0x' prefix.
+    let style = match ctx.len() {
+        1 => "lower",
+        _ => match ctx.get_raw(1) {
+            ValueRef::Integer(0) => "lower",
+            ValueRef::Integer(_) => "0x",
+            _ => text_arg(ctx, 1)?,
+        },
+    };
+    let (prefix, upper) = match style {
+        "lower" => ("", false),
+        "upper" => ("", true),
+        "0x" => ("0x", false),
+        _ => anyhow::bail!(
+            "unknown hex style '{}' (expected 'lower', 'upper' or '0x')",
+            style
+        ),
+    };
+    Ok(ToSqlOutput::Owned(Value::Text(hex::encode(
+        blob, prefix, upper,
+    ))))
+}
+
+/// Parses a big-endian amount of up to 8 bytes into an integer.
+///
+/// Chia amounts are u64 while SQLite integers are i64, so amounts above
+/// i64::MAX raise an error rather than silently turning negative.
+fn chia_amount_int<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mojos = u64::from_be_bytes(amount_bytes(&bytes_arg(ctx, 0)?)?);
+    let mojos = i64::try_from(mojos).map_err(|_| {
+        anyhow::anyhow!(
+            "amount {} does not fit into a signed 64-bit integer \
+             (use chia_amount_text or chia_amount_uint)",
+            mojos
+        )
+    })?;
+    Ok(ToSqlOutput::Owned(Value::Integer(mojos)))
+}
+
+/// Parses a big-endian amount of up to 8 bytes into its exact decimal text.
+fn chia_amount_text<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mojos = u64::from_be_bytes(amount_bytes(&bytes_arg(ctx, 0)?)?);
+    Ok(ToSqlOutput::Owned(Value::Text(mojos.to_string())))
+}
+
+/// Parses a big-endian amount of up to 8 bytes as u64, returning an integer if the
+/// value fits into an i64 and its decimal representation as text otherwise.
+fn chia_amount_uint<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mojos = u64::from_be_bytes(amount_bytes(&bytes_arg(ctx, 0)?)?);
+    Ok(ToSqlOutput::Owned(u64_value(mojos)))
+}
+
+/// Encodes an amount, given as integer or as decimal text above 2^63-1, as
+/// the 8-byte big-endian blob coins are serialized with.
+fn chia_amount_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mojos = u64_arg(ctx, 0)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        mojos.to_be_bytes().to_vec(),
+    )))
+}
+
+/// Parses an amount as CLVM encodes it, e.g. in `CREATE_COIN` conditions: a
+/// big-endian two's complement integer without redundant leading bytes, so
+/// amounts with the high bit set carry an extra zero byte.
+fn chia_amount_from_minimal<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = &*bytes_arg(ctx, 0)?;
+    if blob.first().map_or(false, |b| b & 0x80 != 0) {
+        anyhow::bail!("amount must not be negative");
+    }
+    let start = blob.iter().position(|&b| b != 0).unwrap_or(blob.len());
+    let digits = &blob[start..];
+    if digits.len() > 8 {
+        anyhow::bail!(
+            "amount does not fit into 64 bits ({} significant bytes)",
+            digits.len()
+        );
+    }
+    let mojos = u64::from_be_bytes(amount_bytes(digits)?);
+    Ok(ToSqlOutput::Owned(u64_value(mojos)))
+}
+
+const XCH_DECIMALS: u32 = 12;
+const CAT_DECIMALS: u32 = 3;
+const MAX_DECIMALS: u32 = 18;
+
+/// Formats an amount of mojos as exact decimal amount of a token with
+/// `decimals` decimal places, or with the decimals given as second argument.
+/// Trailing zeros are trimmed.
+fn mojo_to_amount<'a>(ctx: &Context, decimals: Option<u32>) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mojos = mojos_arg(ctx, 0)?;
+    let decimals = decimals.map_or_else(|| decimals_arg(ctx, 1), Ok)?;
+    let amount = format_amount(mojos.into(), decimals);
+    Ok(ToSqlOutput::Owned(Value::Text(amount)))
+}
+
+/// Parses a decimal amount of a token with `decimals` decimal places, or with
+/// the decimals given as second argument, into mojos. Reals are parsed from
+/// their shortest decimal representation, so no floating point arithmetic is
+/// involved.
+fn amount_to_mojo<'a>(ctx: &Context, decimals: Option<u32>) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let amount = match ctx.get_raw(0) {
+        ValueRef::Text(text) => std::str::from_utf8(text)?.to_string(),
+        ValueRef::Real(amount) => amount.to_string(),
+        ValueRef::Integer(amount) => amount.to_string(),
+        other => anyhow::bail!(
+            "expected TEXT, REAL or INTEGER argument, got {}",
+            other.data_type()
+        ),
+    };
+    let decimals = decimals.map_or_else(|| decimals_arg(ctx, 1), Ok)?;
+    let mojos = parse_amount(&amount, decimals)?;
+    Ok(ToSqlOutput::Owned(u64_value(mojos)))
+}
+
+fn decimals_arg(ctx: &Context, idx: usize) -> anyhow::Result<u32> {
+    match u64_arg(ctx, idx)? {
+        decimals if decimals <= u64::from(MAX_DECIMALS) => Ok(decimals as u32),
+        decimals => anyhow::bail!(
+            "decimals must be between 0 and {}, got {}",
+            MAX_DECIMALS,
+            decimals
+        ),
+    }
+}
+
+/// Amounts below this many mojos (0.000001 XCH) are formatted in mojos.
+const FORMAT_MOJO_XCH_THRESHOLD: u64 = 1_000_000;
+
+/// Formats an amount of mojos for display, e.g. '1.75 XCH' or '250 mojo'. The
+/// optional second argument forces the unit, 'mojo' or 'xch' ('auto' picks
+/// by magnitude), and the third limits the fractional digits of XCH amounts,
+/// rounding half up.
+fn format_mojo<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mojos = mojos_arg(ctx, 0)?;
+    let unit = if ctx.len() > 1 {
+        text_arg(ctx, 1)?
+    } else {
+        "auto"
+    };
+    let in_xch = match unit {
+        unit if unit.eq_ignore_ascii_case("auto") => mojos >= FORMAT_MOJO_XCH_THRESHOLD,
+        unit if unit.eq_ignore_ascii_case("xch") => true,
+        unit if unit.eq_ignore_ascii_case("mojo") => false,
+        unit => anyhow::bail!("unknown unit '{}' (expected 'auto', 'mojo' or 'xch')", unit),
+    };
+    let digits = match ctx.len() {
+        3 => match u64_arg(ctx, 2)? {
+            digits if digits <= u64::from(XCH_DECIMALS) => digits as u32,
+            digits => anyhow::bail!(
+                "fractional digits must be between 0 and {}, got {}",
+                XCH_DECIMALS,
+                digits
+            ),
+        },
+        _ => XCH_DECIMALS,
+    };
+    let formatted = if in_xch {
+        // Computed in 128 bits, as rounding up u64::MAX mojos overflows.
+        let scale = 10u128.pow(XCH_DECIMALS - digits);
+        let rounded = (u128::from(mojos) + scale / 2) / scale;
+        format!("{} XCH", format_amount(rounded, digits))
+    } else {
+        format!("{} mojo", mojos)
+    };
+    Ok(ToSqlOutput::Owned(Value::Text(formatted)))
+}
+
+fn format_amount(mojos: u128, decimals: u32) -> String {
+    let scale = 10u128.pow(decimals);
+    let (whole, frac) = (mojos / scale, mojos % scale);
+    if frac == 0 {
+        whole.to_string()
+    } else {
+        let amount = format!("{}.{:0width$}", whole, frac, width = decimals as usize);
+        amount.trim_end_matches('0').to_string()
+    }
+}
+
+fn parse_amount(amount: &str, decimals: u32) -> anyhow::Result<u64> {
+    if amount.starts_with('-') {
+        anyhow::bail!("amount must not be negative, got '{}'", amount);
+    }
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (whole.is_empty() && frac.is_empty()) || !digits(whole) || !digits(frac) {
+        anyhow::bail!("invalid amount '{}'", amount);
+    }
+    if frac.len() > decimals as usize {
+        anyhow::bail!(
+            "amount '{}' has more than {} decimal places",
+            amount,
+            decimals
+        );
+    }
+    let whole = if whole.is_empty() {
+        Ok(0)
+    } else {
+        whole.parse::<u64>()
+    };
+    let frac = format!("{:0<width$}", frac, width = decimals as usize);
+    let frac = if frac.is_empty() {
+        0
+    } else {
+        frac.parse::<u64>()?
+    };
+    whole
+        .ok()
+        .and_then(|whole| whole.checked_mul(10u64.pow(decimals)))
+        .and_then(|mojos| mojos.checked_add(frac))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "amount '{}' exceeds the largest amount with {} decimal places, {}",
+                amount,
+                decimals,
+                format_amount(u64::MAX.into(), decimals)
+            )
+        })
+}
+
+/// Returns an amount of mojos given as integer, decimal text or a big-endian
+/// blob of up to 8 bytes.
+fn mojos_arg(ctx: &Context, idx: usize) -> anyhow::Result<u64> {
+    match ctx.get_raw(idx) {
+        ValueRef::Blob(blob) => Ok(u64::from_be_bytes(amount_bytes(blob)?)),
+        _ => u64_arg(ctx, idx),
+    }
+}
+
+/// Left-pads a big-endian amount blob to 8 bytes. Chia stores amounts
+/// minimally encoded in several places, so shorter blobs (including the empty
+/// blob for zero) are common.
+fn amount_bytes(blob: &[u8]) -> anyhow::Result<[u8; 8]> {
+    if blob.len() > 8 {
+        anyhow::bail!(
+            "amount blob must be at most 8 bytes, got {} bytes",
+            blob.len()
+        );
+    }
+    let mut bytes = [0u8; 8];
+    bytes[8 - blob.len()..].copy_from_slice(blob);
+    Ok(bytes)
+}
+
+fn be_uint_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    Ok(ToSqlOutput::Owned(uint_value(&bytes_arg(ctx, 0)?)?))
+}
+
+fn le_uint_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut bytes = bytes_arg(ctx, 0)?.into_owned();
+    bytes.reverse();
+    Ok(ToSqlOutput::Owned(uint_value(&bytes)?))
+}
+
+/// Parses a big-endian unsigned integer. Blobs of up to 8 bytes are read like
+/// `chia_amount_uint` does; longer ones only if the value fits into an i64.
+fn uint_value(be_bytes: &[u8]) -> anyhow::Result<Value> {
+    if be_bytes.len() <= 8 {
+        return Ok(u64_value(u64::from_be_bytes(amount_bytes(be_bytes)?)));
+    }
+    let start = be_bytes
+        .iter()
+        .position(|&b| b != 0)
+        .unwrap_or(be_bytes.len());
+    let significant = &be_bytes[start..];
+    if significant.len() > 8 || significant.first().map_or(false, |b| b & 0x80 != 0) {
+        anyhow::bail!(
+            "{}-byte integer does not fit into a signed 64-bit integer",
+            be_bytes.len()
+        );
+    }
+    Ok(u64_value(u64::from_be_bytes(amount_bytes(significant)?)))
+}
+
+/// Encodes a non-negative integer (or its decimal text, as returned for
+/// values above 2^63-1) as a zero-padded big-endian blob of `width` bytes.
+fn uint_to_be_blob_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let value = u64_arg(ctx, 0)?;
+    let width = ctx.get::<i64>(1)?;
+    let width = usize::try_from(width)
+        .map_err(|_| anyhow::anyhow!("width must not be negative, got {}", width))?;
+    let bytes = value.to_be_bytes();
+    let significant = &bytes[(value.leading_zeros() / 8) as usize..];
+    if significant.len() > width {
+        anyhow::bail!("value {} does not fit into {} bytes", value, width);
+    }
+    let mut out = vec![0u8; width];
+    out[width - significant.len()..].copy_from_slice(significant);
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+/// Returns a non-negative integer argument, or its decimal text for values
+/// above 2^63-1, as a u64.
+fn u64_arg(ctx: &Context, idx: usize) -> anyhow::Result<u64> {
+    match ctx.get_raw(idx) {
+        ValueRef::Integer(n) => {
+            u64::try_from(n).map_err(|_| anyhow::anyhow!("value must not be negative, got {}", n))
+        }
+        ValueRef::Text(text) => {
+            let text = std::str::from_utf8(text)?;
+            text.parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("invalid unsigned integer '{}': {}", text, e))
+        }
+        other => anyhow::bail!("expected INTEGER argument, got {}", other.data_type()),
+    }
+}
+
+/// Converts a u64 into a SQLite value without loss: an integer if it fits
+/// into an i64, decimal text otherwise.
+fn u64_value(n: u64) -> Value {
+    match i64::try_from(n) {
+        Ok(n) => Value::Integer(n),
+        Err(_) => Value::Text(n.to_string()),
+    }
+}
+
+/// Converts a u128 into a SQLite value without loss, like `u64_value`.
+fn u128_value(n: u128) -> Value {
+    match i64::try_from(n) {
+        Ok(n) => Value::Integer(n),
+        Err(_) => Value::Text(n.to_string()),
+    }
+}
+
+const MOJOS_PER_XCH: u64 = 1_000_000_000_000;
+/// Rewards halve every three years of 32 blocks per 10 minutes.
+const BLOCKS_PER_HALVING: u64 = 3 * 32 * 6 * 24 * 365;
+/// The prefarm, paid out as the reward of the genesis block.
+const PREFARM_MOJOS: u64 = 21_000_000 * MOJOS_PER_XCH;
+
+/// Returns the pool and base farmer rewards (without fees) of the block at
+/// `height`, as in chia-blockchain's `block_rewards.py`: 2 XCH per block at
+/// first, halving every `BLOCKS_PER_HALVING` blocks four times, then 0.125 XCH
+/// forever. The pool gets 7/8 of the reward and the farmer 1/8.
+fn block_rewards(height: u64) -> (u64, u64) {
+    let reward = if height == 0 {
+        u128::from(PREFARM_MOJOS)
+    } else {
+        let halvings = (height / BLOCKS_PER_HALVING).min(HALVINGS);
+        u128::from(2 * MOJOS_PER_XCH >> halvings)
+    };
+    ((reward * 7 / 8) as u64, (reward / 8) as u64)
+}
+
+/// Returns a reward, as computed by `reward`, of the block at the height in
+/// the first argument.
+fn block_reward_fn<'a>(ctx: &Context, reward: fn(u64) -> u128) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let height = u64_arg(ctx, 0).map_err(|e| anyhow::anyhow!("invalid block height: {}", e))?;
+    Ok(ToSqlOutput::Owned(u128_value(reward(height))))
+}
+
+/// Number of halvings before the block reward stays the same forever.
+const HALVINGS: u64 = 4;
+
+/// Number of rows `chia_reward_schedule` lists: the genesis block, one per
+/// period before each halving and the final, open-ended era.
+const REWARD_SCHEDULE_ERAS: u64 = HALVINGS + 2;
+
+/// Returns the reward eras `chia_reward_schedule` lists as (first height,
+/// last height, reward). The last era ends at the largest SQLite integer, so
+/// every height falls into exactly one era.
+fn reward_schedule() -> Vec<(u64, u64, u128)> {
+    let total = |height| {
+        let (pool, farmer) = block_rewards(height);
+        u128::from(pool) + u128::from(farmer)
+    };
+    let mut eras = vec![(0, 0, total(0))];
+    for period in 0..=HALVINGS {
+        let start = (period * BLOCKS_PER_HALVING).max(1);
+        let end = if period < HALVINGS {
+            (period + 1) * BLOCKS_PER_HALVING - 1
+        } else {
+            i64::MAX as u64
+        };
+        eras.push((start, end, total(start)));
+    }
+    eras
+}
+
+/// Eponymous virtual table listing the block reward eras, generated from
+/// `block_rewards` so it agrees with `chia_block_reward`.
+#[repr(C)]
+struct RewardScheduleTab {
+    // Must come first; only SQLite accesses it.
+    #[allow(dead_code)]
+    base: rusqlite::ffi::sqlite3_vtab,
+}
+
+unsafe impl rusqlite::vtab::VTab for RewardScheduleTab {
+    type Aux = ();
+    type Cursor = RewardScheduleCursor;
+
+    fn connect(
+        _db: &mut rusqlite::vtab::VTabConnection,
+        _aux: Option<&()>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, RewardScheduleTab)> {
+        let schema = "CREATE TABLE x(start_height INTEGER, end_height INTEGER, \
+                      reward_mojos, reward_xch_text TEXT)";
+        let vtab = RewardScheduleTab {
+            base: rusqlite::ffi::sqlite3_vtab::default(),
+        };
+        Ok((schema.to_string(), vtab))
+    }
+
+    fn best_index(&self, info: &mut rusqlite::vtab::IndexInfo) -> rusqlite::Result<()> {
+        info.set_estimated_cost(REWARD_SCHEDULE_ERAS as f64);
+        Ok(())
+    }
+
+    fn open(&self) -> rusqlite::Result<RewardScheduleCursor> {
+        Ok(RewardScheduleCursor {
+            base: rusqlite::ffi::sqlite3_vtab_cursor::default(),
+            eras: reward_schedule(),
+            row: 0,
+        })
+    }
+}
+
+#[repr(C)]
+struct RewardScheduleCursor {
+    // Must come first; only SQLite accesses it.
+    #[allow(dead_code)]
+    base: rusqlite::ffi::sqlite3_vtab_cursor,
+    eras: Vec<(u64, u64, u128)>,
+    row: usize,
+}
+
+unsafe impl rusqlite::vtab::VTabCursor for RewardScheduleCursor {
+    fn filter(
+        &mut self,
+        _idx_num: std::os::raw::c_int,
+        _idx_str: Option<&str>,
+        _args: &rusqlite::vtab::Values<'_>,
+    ) -> rusqlite::Result<()> {
+        self.row = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.row += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row >= self.eras.len()
+    }
+
+    fn column(
+        &self,
+        ctx: &mut rusqlite::vtab::Context,
+        i: std::os::raw::c_int,
+    ) -> rusqlite::Result<()> {
+        let (start, end, reward) = self.eras[self.row];
+        match i {
+            0 => ctx.set_result(&u64_value(start)),
+            1 => ctx.set_result(&u64_value(end)),
+            2 => ctx.set_result(&u128_value(reward)),
+            _ => ctx.set_result(&format_amount(reward, XCH_DECIMALS)),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(self.row as i64)
+    }
+}
+
+/// Computes an announcement ID the way consensus does for
+/// `CREATE_COIN_ANNOUNCEMENT` and `CREATE_PUZZLE_ANNOUNCEMENT`, i.e.
+/// `sha256(coin_id || message)` or `sha256(puzzle_hash || message)`. `what`
+/// names the 32-byte first argument in errors.
+fn announcement_id<'a>(ctx: &Context, what: &str) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let id = bytes_arg(ctx, 0)?;
+    if id.len() != 32 {
+        anyhow::bail!("{} must be 32 bytes long, got {} bytes", what, id.len());
+    }
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(&id);
+    hasher.update(&bytes_arg(ctx, 1)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(hasher.finalize().to_vec())))
+}
+
+/// Returns the message an AGG_SIG_* condition (opcode 43 to 50) requires a
+/// signature of, given the coin whose puzzle output it and the network.
+fn chia_agg_sig_message<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let opcode = ctx.get::<i64>(0)?;
+    let msg = blob_or_text_arg(ctx, 1)?;
+    let coin = conditions::Coin {
+        parent_coin_info: bytes32_arg(ctx, 2, "parent coin ID")?,
+        puzzle_hash: bytes32_arg(ctx, 3, "puzzle hash")?,
+        amount: u64_arg(ctx, 4)?,
+    };
+    let genesis_challenge = genesis_challenge_arg(ctx, 5)?;
+    let message = u8::try_from(opcode)
+        .ok()
+        .and_then(|opcode| conditions::agg_sig_message(opcode, msg, &coin, &genesis_challenge))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "opcode must be an AGG_SIG condition (43 to 50), got {}",
+                opcode
+            )
+        })?;
+    Ok(ToSqlOutput::Owned(Value::Blob(message)))
+}
+
+/// Returns 1 if the aggregated signature of a serialized spend bundle covers
+/// the AGG_SIG_* conditions its puzzles output on the given network, and 0
+/// otherwise.
+#[cfg(feature = "clvm")]
+fn chia_spendbundle_verify_sig<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let bundle = bytes_arg(ctx, 0)?;
+    let genesis_challenge = genesis_challenge_arg(ctx, 1)?;
+    let valid = conditions::verify_spend_bundle(&bundle, &genesis_challenge)?;
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
+
+/// Returns a 32-byte BLOB argument; `what` names it in errors.
+fn bytes32_arg(ctx: &Context, idx: usize, what: &str) -> anyhow::Result<[u8; 32]> {
+    let blob = blob_arg(ctx, idx)?;
+    <[u8; 32]>::try_from(blob)
+        .map_err(|_| anyhow::anyhow!("{} must be 32 bytes long, got {} bytes", what, blob.len()))
+}
+
+/// Returns the genesis challenge of a network given by name, `'mainnet'` or
+/// `'testnet11'`, or as 32-byte blob.
+fn genesis_challenge_arg(ctx: &Context, idx: usize) -> anyhow::Result<[u8; 32]> {
+    match ctx.get_raw(idx) {
+        ValueRef::Text(b"mainnet") => Ok(conditions::MAINNET_GENESIS_CHALLENGE),
+        ValueRef::Text(b"testnet11") => Ok(conditions::TESTNET11_GENESIS_CHALLENGE),
+        ValueRef::Text(name) => anyhow::bail!(
+            "unknown network '{}' (expected 'mainnet', 'testnet11' or a genesis challenge)",
+            String::from_utf8_lossy(name)
+        ),
+        _ => bytes32_arg(ctx, idx, "genesis challenge"),
+    }
+}
+
+fn chia_fullblock_json<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use anyhow::Context as _;
+    use chia_traits::streamable::Streamable;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = &*bytes_arg(ctx, 0)?;
+    let block = chia_protocol::FullBlock::parse::<true>(&mut Cursor::new(blob))
+        .with_context(|| format!("failed to parse FullBlock from {}-byte blob", blob.len()))?;
+    let json: String = serde_json::to_string(&block)?;
+    Ok(ToSqlOutput::Owned(Value::Text(json)))
+}
+
+/// Parses a serialized coin, 72 bytes: parent coin ID, puzzle hash and amount.
+/// Its JSON holds the amount as number, which serde_json writes exactly for
+/// the whole u64 range.
+fn chia_coin_json<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use anyhow::Context as _;
+    use chia_traits::streamable::Streamable;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = &*bytes_arg(ctx, 0)?;
+    let mut cursor = Cursor::new(blob);
+    let coin = chia_protocol::Coin::parse::<true>(&mut cursor)
+        .with_context(|| format!("failed to parse Coin from {}-byte blob", blob.len()))?;
+    if cursor.position() as usize != blob.len() {
+        anyhow::bail!(
+            "trailing bytes after Coin ({} of {} bytes used)",
+            cursor.position(),
+            blob.len()
+        );
+    }
+    let json: String = serde_json::to_string(&coin)?;
+    Ok(ToSqlOutput::Owned(Value::Text(json)))
+}
+
+/// Computes a BLAKE2b digest, 32 bytes long unless the optional second
+/// argument asks for a different length (1 to 64 bytes). Like `sha256sum`,
+/// text is hashed as its UTF-8 bytes.
+#[cfg(feature = "blake2")]
+fn blake2b_256<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use blake2::digest::{Update, VariableOutput};
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = blob_or_text_arg(ctx, 0)?;
+    let len = if ctx.len() > 1 {
+        ctx.get::<i64>(1)?
+    } else {
+        32
+    };
+    let mut hasher = usize::try_from(len)
+        .ok()
+        .and_then(|len| blake2::Blake2bVar::new(len).ok())
+        .ok_or_else(|| anyhow::anyhow!("digest length must be between 1 and 64, got {}", len))?;
+    hasher.update(data);
+    let mut out = vec![0u8; hasher.output_size()];
+    hasher
+        .finalize_variable(&mut out)
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+/// Upper bound on the output length `blake3` accepts for extended output.
+#[cfg(feature = "blake3")]
+const BLAKE3_MAX_OUTPUT_LEN: usize = 65536;
+
+/// Inputs at least this long are hashed on multiple threads.
+#[cfg(feature = "blake3")]
+const BLAKE3_PARALLEL_MIN_LEN: usize = 128 * 1024;
+
+/// Computes a BLAKE3 digest, 32 bytes long unless the optional second argument
+/// requests a different length of extended (XOF) output.
+#[cfg(feature = "blake3")]
+fn blake3_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = blob_or_text_arg(ctx, 0)?;
+    let mut hasher = blake3::Hasher::new();
+    // Spreading small inputs over threads costs more than it saves.
+    if data.len() >= BLAKE3_PARALLEL_MIN_LEN {
+        hasher.update_rayon(data);
+    } else {
+        hasher.update(data);
+    }
+    if ctx.len() == 1 {
+        return Ok(ToSqlOutput::Owned(Value::Blob(
+            hasher.finalize().as_bytes().to_vec(),
+        )));
+    }
+    let len = ctx.get::<i64>(1)?;
+    let len = match usize::try_from(len) {
+        Ok(len) if (1..=BLAKE3_MAX_OUTPUT_LEN).contains(&len) => len,
+        _ => anyhow::bail!(
+            "output length must be between 1 and {}, got {}",
+            BLAKE3_MAX_OUTPUT_LEN,
+            len
+        ),
+    };
+    let mut out = vec![0u8; len];
+    hasher.finalize_xof().fill(&mut out);
+    Ok(ToSqlOutput::Owned(Value::Blob(out)))
+}
+
+/// Computes the original Keccak-256 digest used by Ethereum, which differs
+/// from the standardized SHA3-256 in its padding.
+#[cfg(feature = "sha3")]
+fn keccak256<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha3::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = sha3::Keccak256::digest(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+#[cfg(feature = "sha3")]
+fn sha3_256<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha3::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = sha3::Sha3_256::digest(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+/// Computes `ripemd160(sha256(blob))` as used for Bitcoin addresses.
+#[cfg(feature = "ripemd")]
+fn hash160<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use ripemd::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = ripemd::Ripemd160::digest(sha2::Sha256::digest(blob_or_text_arg(ctx, 0)?));
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+#[cfg(feature = "ripemd")]
+fn ripemd160<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use ripemd::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = ripemd::Ripemd160::digest(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+/// Computes the tree hash of a serialized CLVM program, e.g. a puzzle hash from
+/// a puzzle reveal.
+fn clvm_tree_hash_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let hash = clvm::tree_hash(&bytes_arg(ctx, 0)?)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(hash.to_vec())))
+}
+
+/// Decodes a CLVM integer atom: big-endian two's complement, where the empty
+/// atom is 0. Redundant leading bytes are tolerated.
+fn clvm_int_decode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let atom = &*bytes_arg(ctx, 0)?;
+    let negative = atom.first().map_or(false, |b| b & 0x80 != 0);
+    let pad = if negative { 0xff } else { 0x00 };
+    let mut start = 0;
+    while atom.len() - start > 8 && atom[start] == pad {
+        start += 1;
+    }
+    let digits = &atom[start..];
+    // Dropping a padding byte must not flip the sign.
+    if digits.len() > 8
+        || digits
+            .first()
+            .map_or(false, |b| (b & 0x80 != 0) != negative)
+    {
+        anyhow::bail!(
+            "CLVM integer of {} bytes does not fit into a signed 64-bit integer",
+            atom.len()
+        );
+    }
+    let mut bytes = [pad; 8];
+    bytes[8 - digits.len()..].copy_from_slice(digits);
+    Ok(ToSqlOutput::Owned(Value::Integer(i64::from_be_bytes(
+        bytes,
+    ))))
+}
+
+/// Encodes an integer as canonical CLVM atom, i.e. with as few bytes as
+/// possible to keep the sign.
+fn clvm_int_encode_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let n = match ctx.get_raw(0) {
+        ValueRef::Integer(n) => n,
+        other => anyhow::bail!("expected INTEGER argument, got {}", other.data_type()),
+    };
+    Ok(ToSqlOutput::Owned(Value::Blob(clvm_int_bytes(n))))
+}
+
+fn clvm_int_bytes(n: i64) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() {
+        let redundant = match bytes[start] {
+            0x00 => bytes.get(start + 1).map_or(true, |b| b & 0x80 == 0),
+            0xff => bytes.get(start + 1).map_or(false, |b| b & 0x80 != 0),
+            _ => false,
+        };
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+fn sha224<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = sha2::Sha224::digest(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+/// Aggregate hashing the concatenation of all non-NULL values in a group, in
+/// the order SQLite feeds them. An empty group yields the hash of no bytes.
+struct Sha256Agg;
+
+impl rusqlite::functions::Aggregate<sha2::Sha256, Vec<u8>> for Sha256Agg {
+    fn init(&self) -> sha2::Sha256 {
+        sha2::Sha256::default()
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, hasher: &mut sha2::Sha256) -> rusqlite::Result<()> {
+        use sha2::Digest;
+        match ctx.get_raw(0) {
+            ValueRef::Null => {}
+            ValueRef::Blob(bytes) | ValueRef::Text(bytes) => hasher.update(bytes),
+            other => {
+                return Err(ah(anyhow::anyhow!(
+                    "expected BLOB or TEXT argument, got {}",
+                    other.data_type()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn finalize(&self, hasher: Option<sha2::Sha256>) -> rusqlite::Result<Vec<u8>> {
+        use sha2::Digest;
+        Ok(hasher.unwrap_or_default().finalize().to_vec())
+    }
+}
+
+/// Aggregate summing amounts of mojos exactly, unlike `sum()`, which switches
+/// to floating point beyond 2^63-1. NULL values are skipped, and a group of
+/// only NULL values yields NULL.
+struct SumMojoAgg;
+
+impl rusqlite::functions::Aggregate<Option<u128>, Value> for SumMojoAgg {
+    fn init(&self) -> Option<u128> {
+        None
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, sum: &mut Option<u128>) -> rusqlite::Result<()> {
+        if ctx.get_raw(0).data_type() == Type::Null {
+            return Ok(());
+        }
+        let mojos = mojos_arg(ctx, 0).map_err(ah)?;
+        // Can't overflow before summing 2^64 maximal amounts.
+        *sum = Some(sum.unwrap_or(0) + u128::from(mojos));
+        Ok(())
+    }
+
+    fn finalize(&self, sum: Option<Option<u128>>) -> rusqlite::Result<Value> {
+        Ok(sum.flatten().map_or(Value::Null, u128_value))
+    }
+}
+
+/// Window function summing signed amounts of mojos exactly, e.g. credits and
+/// debits into a running balance. Keeps the sum and the number of non-NULL
+/// values, so frames without any yield NULL like `sum()`.
+struct RunningMojoBalance;
+
+impl rusqlite::functions::Aggregate<(i128, u64), Value> for RunningMojoBalance {
+    fn init(&self) -> (i128, u64) {
+        (0, 0)
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, balance: &mut (i128, u64)) -> rusqlite::Result<()> {
+        if ctx.get_raw(0).data_type() != Type::Null {
+            balance.0 += mojo_delta_arg(ctx, 0).map_err(ah)?;
+            balance.1 += 1;
+        }
+        Ok(())
+    }
+
+    fn finalize(&self, balance: Option<(i128, u64)>) -> rusqlite::Result<Value> {
+        rusqlite::functions::WindowAggregate::value(self, balance.as_ref())
+    }
+}
+
+impl rusqlite::functions::WindowAggregate<(i128, u64), Value> for RunningMojoBalance {
+    fn value(&self, balance: Option<&(i128, u64)>) -> rusqlite::Result<Value> {
+        Ok(match balance {
+            None | Some((_, 0)) => Value::Null,
+            Some(&(sum, _)) => match i64::try_from(sum) {
+                Ok(sum) => Value::Integer(sum),
+                Err(_) => Value::Text(sum.to_string()),
+            },
+        })
+    }
+
+    fn inverse(&self, ctx: &mut Context<'_>, balance: &mut (i128, u64)) -> rusqlite::Result<()> {
+        if ctx.get_raw(0).data_type() != Type::Null {
+            balance.0 -= mojo_delta_arg(ctx, 0).map_err(ah)?;
+            balance.1 -= 1;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a signed amount of mojos given as integer, decimal text (up to
+/// ±u64::MAX) or a big-endian blob of up to 8 bytes, which is never negative.
+fn mojo_delta_arg(ctx: &Context, idx: usize) -> anyhow::Result<i128> {
+    match ctx.get_raw(idx) {
+        ValueRef::Integer(n) => Ok(i128::from(n)),
+        ValueRef::Text(text) => {
+            let text = std::str::from_utf8(text)?;
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(digits) => (-1, digits),
+                None => (1, text),
+            };
+            let mojos = digits
+                .parse::<u64>()
+                .map_err(|e| anyhow::anyhow!("invalid amount '{}': {}", text, e))?;
+            Ok(sign * i128::from(mojos))
+        }
+        ValueRef::Blob(blob) => Ok(i128::from(u64::from_be_bytes(amount_bytes(blob)?))),
+        other => anyhow::bail!(
+            "expected INTEGER, TEXT or BLOB argument, got {}",
+            other.data_type()
+        ),
+    }
+}
+
+/// Aggregate collecting 32-byte items into the merkle set whose root is
+/// committed to as `additions_root` and `removals_root` in blocks. NULL values
+/// are skipped.
+struct MerkleSetRootAgg;
+
+impl rusqlite::functions::Aggregate<Vec<[u8; 32]>, Vec<u8>> for MerkleSetRootAgg {
+    fn init(&self) -> Vec<[u8; 32]> {
+        Vec::new()
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, items: &mut Vec<[u8; 32]>) -> rusqlite::Result<()> {
+        if ctx.get_raw(0).data_type() == Type::Null {
+            return Ok(());
+        }
+        let item = bytes_arg(ctx, 0).map_err(ah)?;
+        let item = <[u8; 32]>::try_from(&item[..]).map_err(|_| {
+            ah(anyhow::anyhow!(
+                "merkle set items must be 32 bytes long, got {} bytes",
+                item.len()
+            ))
+        })?;
+        items.push(item);
+        Ok(())
+    }
+
+    fn finalize(&self, items: Option<Vec<[u8; 32]>>) -> rusqlite::Result<Vec<u8>> {
+        Ok(merkle_set::root(&mut items.unwrap_or_default()).to_vec())
+    }
+}
+
+/// Returns 1 if a merkle set proof for `item` is valid for `root` and shows
+/// the item to be included (or excluded, if the optional fourth argument is
+/// false), and 0 otherwise, including for malformed proofs.
+fn merkle_set_verify<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let hash_arg = |idx: usize, what: &str| -> anyhow::Result<[u8; 32]> {
+        let bytes = bytes_arg(ctx, idx)?;
+        <[u8; 32]>::try_from(&bytes[..]).map_err(|_| {
+            anyhow::anyhow!("{} must be 32 bytes long, got {} bytes", what, bytes.len())
+        })
+    };
+    let root = hash_arg(0, "merkle set root")?;
+    let item = hash_arg(1, "merkle set item")?;
+    let included = ctx.len() < 4 || ctx.get::<bool>(3)?;
+    let valid = merkle_set::verify_proof(&root, &item, &bytes_arg(ctx, 2)?, included);
+    Ok(ToSqlOutput::Owned(Value::Integer(valid.into())))
+}
+
+/// Computes HMAC-SHA256 (RFC 2104) of the second argument keyed with the
+/// first. Text is used as its UTF-8 bytes.
+fn hmac_sha256<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use hmac::Mac;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(blob_or_text_arg(ctx, 0)?)
+        .expect("HMAC accepts keys of any length");
+    mac.update(blob_or_text_arg(ctx, 1)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        mac.finalize().into_bytes().to_vec(),
+    )))
+}
+
+/// Hashes the file at the given path, reading it in chunks.
+fn sha256_file<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use anyhow::Context as _;
+    use sha2::Digest;
+    use std::io::Read;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let path = text_arg(ctx, 0)?;
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("failed to open '{}'", path))?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .with_context(|| format!("failed to read '{}'", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(hasher.finalize().to_vec())))
+}
+
+/// Hashes the concatenation of all arguments. Text is hashed as its UTF-8
+/// bytes; integers are rejected since their byte representation is ambiguous.
+fn sha256sum<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut hasher = sha2::Sha256::new();
+    for i in 0..ctx.len() {
+        hasher.update(blob_or_text_arg(ctx, i)?);
+    }
+    Ok(ToSqlOutput::Owned(Value::Blob(hasher.finalize().to_vec())))
+}
+
+/// Returns the first `n` bytes of the SHA256 digest, or all 32 if `n` is
+/// larger, e.g. for compact fingerprints.
+fn sha256_prefix<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let n = ctx.get::<i64>(1)?;
+    let n = usize::try_from(n)
+        .map_err(|_| anyhow::anyhow!("prefix length must not be negative, got {}", n))?;
+    let digest = sha2::Sha256::digest(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        digest[..n.min(digest.len())].to_vec(),
+    )))
+}
+
+fn sha512sum<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use sha2::Digest;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = sha2::Sha512::digest(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+fn double_sha256_fn<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let digest = double_sha256(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Blob(digest.to_vec())))
+}
+
+fn double_sha256(data: &[u8]) -> [u8; 32] {
+    use sha2::Digest;
+    sha2::Sha256::digest(sha2::Sha256::digest(data)).into()
+}
+
+/// Computes the CRC-32 (IEEE) checksum as a non-negative integer.
+fn crc32<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let checksum = crc32fast::hash(blob_or_text_arg(ctx, 0)?);
+    Ok(ToSqlOutput::Owned(Value::Integer(checksum.into())))
+}
+
+/// Computes the 64-bit XXH3 hash with an optional seed. Both the hash and the
+/// seed are reinterpreted as two's complement, since SQLite integers are
+/// signed.
+fn xxh3_64<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let data = blob_or_text_arg(ctx, 0)?;
+    let hash = if ctx.len() > 1 {
+        xxhash_rust::xxh3::xxh3_64_with_seed(data, ctx.get::<i64>(1)? as u64)
+    } else {
+        xxhash_rust::xxh3::xxh3_64(data)
+    };
+    Ok(ToSqlOutput::Owned(Value::Integer(hash as i64)))
+}
+
+/// Compresses a blob (or the UTF-8 bytes of text) into a zstd frame at an
+/// optional level. The frame header records the uncompressed size unless the
+/// third argument is false.
+fn zstd_compress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let level = if ctx.len() > 1 {
+        let range = zstd::compression_level_range();
+        let level = ctx.get::<i64>(1)?;
+        if level < (*range.start()).into() || level > (*range.end()).into() {
+            anyhow::bail!(
+                "zstd level must be between {} and {}, got {}",
+                range.start(),
+                range.end(),
+                level
+            );
+        }
+        level as i32
+    } else {
+        zstd::DEFAULT_COMPRESSION_LEVEL
+    };
+    let content_size = ctx.len() < 3 || ctx.get::<bool>(2)?;
+    let mut compressor = zstd::bulk::Compressor::new(level)?;
+    compressor.set_parameter(zstd::stream::raw::CParameter::ContentSizeFlag(content_size))?;
+    Ok(ToSqlOutput::Owned(Value::Blob(
+        compressor.compress(blob_or_text_arg(ctx, 0)?)?,
+    )))
+}
+
+fn zstd_decompress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let max_bytes = max_bytes_arg(ctx, 1)?;
+    let data = bytes_arg(ctx, 0)?;
+    let decoder = zstd::stream::read::Decoder::new(&data[..])?;
+    Ok(ToSqlOutput::Owned(Value::Blob(read_at_most_with_hint(
+        decoder,
+        max_bytes,
+        zstd_size_hint(&data),
+    )?)))
+}
+
+/// Upper bound on the compression ratio a frame header's content size is
+/// trusted up to when reserving output.
+const ZSTD_SIZE_HINT_MAX_RATIO: u64 = 64;
+
+/// Returns the number of bytes to reserve for decompressing `data`. Frames
+/// usually record their size, so the output can be allocated once instead of
+/// doubling its way up while the decoder streams into it. The header is
+/// untrusted though, so the hint is capped relative to the input: a 20-byte
+/// blob claiming gigabytes doesn't reserve them before decoding fails.
+fn zstd_size_hint(data: &[u8]) -> u64 {
+    match zstd::zstd_safe::get_frame_content_size(data) {
+        Ok(Some(size)) => size.min((data.len() as u64).saturating_mul(ZSTD_SIZE_HINT_MAX_RATIO)),
+        _ => 0,
+    }
+}
+
+/// Compresses a blob (or the UTF-8 bytes of text) with brotli at an optional
+/// quality from 0 to 11 (the default).
+#[cfg(feature = "brotli")]
+fn brotli_compress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use std::io::Write;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let quality = if ctx.len() > 1 {
+        let quality = ctx.get::<i64>(1)?;
+        u32::try_from(quality)
+            .ok()
+            .filter(|quality| *quality <= 11)
+            .ok_or_else(|| {
+                anyhow::anyhow!("brotli quality must be between 0 and 11, got {}", quality)
+            })?
+    } else {
+        11
+    };
+    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, quality, BROTLI_LGWIN);
+    encoder.write_all(blob_or_text_arg(ctx, 0)?)?;
+    // Finishes the stream.
+    Ok(ToSqlOutput::Owned(Value::Blob(encoder.into_inner())))
+}
+
+/// Window size (as log2) used for brotli compression, the encoder's default.
+#[cfg(feature = "brotli")]
+const BROTLI_LGWIN: u32 = 22;
+
+#[cfg(feature = "brotli")]
+fn brotli_decompress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let max_bytes = max_bytes_arg(ctx, 1)?;
+    let decoder = brotli::Decompressor::new(&bytes_arg(ctx, 0)?[..], 4096);
+    Ok(ToSqlOutput::Owned(Value::Blob(read_at_most(
+        decoder, max_bytes,
+    )?)))
+}
+
+/// Compresses a blob (or the UTF-8 bytes of text) into a gzip file at an
+/// optional level from 0 (no compression) to 9.
+#[cfg(feature = "gzip")]
+fn gzip_compress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use std::io::Write;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let level = if ctx.len() > 1 {
+        let level = ctx.get::<i64>(1)?;
+        let level = u32::try_from(level)
+            .ok()
+            .filter(|level| *level <= 9)
+            .ok_or_else(|| anyhow::anyhow!("gzip level must be between 0 and 9, got {}", level))?;
+        flate2::Compression::new(level)
+    } else {
+        flate2::Compression::default()
+    };
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+    encoder.write_all(blob_or_text_arg(ctx, 0)?)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(encoder.finish()?)))
+}
+
+/// Returns the decompressed size recorded in a zstd frame header, or NULL if
+/// the frame doesn't record it.
+fn zstd_frame_content_size<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let size = zstd::zstd_safe::get_frame_content_size(&bytes_arg(ctx, 0)?)
+        .map_err(|_| anyhow::anyhow!("invalid or truncated zstd frame header"))?;
+    match size {
+        Some(size) => Ok(ToSqlOutput::Owned(Value::Integer(i64::try_from(size)?))),
+        None => Ok(null()),
+    }
+}
+
+/// Decompresses a zstd frame compressed against the dictionary given as
+/// second argument.
+fn zstd_decompress_with_dict<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let max_bytes = max_bytes_arg(ctx, 2)?;
+    let dict = zstd::dict::DecoderDictionary::copy(&bytes_arg(ctx, 1)?);
+    let data = bytes_arg(ctx, 0)?;
+    let decoder = zstd::stream::read::Decoder::with_prepared_dictionary(&data[..], &dict)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(read_at_most(
+        decoder, max_bytes,
+    )?)))
+}
+
+/// Decompresses a gzip file, or several concatenated ones like `gzip -d`.
+#[cfg(feature = "gzip")]
+fn gzip_decompress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let max_bytes = max_bytes_arg(ctx, 1)?;
+    let decoder = flate2::read::MultiGzDecoder::new(&bytes_arg(ctx, 0)?[..]);
+    Ok(ToSqlOutput::Owned(Value::Blob(read_at_most(
+        decoder, max_bytes,
+    )?)))
+}
+
+/// Decompresses a raw deflate stream, without gzip or zlib framing.
+#[cfg(feature = "gzip")]
+fn inflate_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let max_bytes = max_bytes_arg(ctx, 1)?;
+    let decoder = flate2::read::DeflateDecoder::new(&bytes_arg(ctx, 0)?[..]);
+    Ok(ToSqlOutput::Owned(Value::Blob(read_at_most(
+        decoder, max_bytes,
+    )?)))
+}
+
+/// Compresses a blob (or the UTF-8 bytes of text) into an LZ4 frame.
+#[cfg(feature = "lz4")]
+fn lz4_compress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    use std::io::Write;
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    encoder.write_all(blob_or_text_arg(ctx, 0)?)?;
+    Ok(ToSqlOutput::Owned(Value::Blob(encoder.finish()?)))
+}
+
+/// Decompresses an LZ4 frame, as written by the `lz4` tool. Raw LZ4 blocks
+/// lack a header telling their size and aren't supported.
+#[cfg(feature = "lz4")]
+fn lz4_decompress_blob<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let max_bytes = max_bytes_arg(ctx, 1)?;
+    let decoder = lz4_flex::frame::FrameDecoder::new(&bytes_arg(ctx, 0)?[..]);
+    Ok(ToSqlOutput::Owned(Value::Blob(read_at_most(
+        decoder, max_bytes,
+    )?)))
+}
+
+/// Names the compression format of a blob by its magic bytes, so queries can
+/// pick the matching decompression function. Brotli streams have no magic
+/// bytes; with the `brotli` feature, small blobs that decode as brotli are
+/// reported.
+fn compression_format<'a>(ctx: &Context) -> anyhow::Result<ToSqlOutput<'a>> {
+    if has_null_arg(ctx) {
+        return Ok(null());
+    }
+    let blob = blob_arg(ctx, 0)?;
+    let format = if blob.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        "zstd"
+    } else if blob.starts_with(&[0x1f, 0x8b, 0x08]) {
+        "gzip"
+    } else if blob.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+        "lz4"
+    } else if is_brotli(blob) {
+        "brotli"
+    } else {
+        "none"
+    };
+    Ok(ToSqlOutput::Owned(Value::Text(format.to_string())))
+}
+
+/// Upper bound on both the size of a blob `compression_format` probes for
+/// brotli and the output it decodes while doing so.
+#[cfg(feature = "brotli")]
+const BROTLI_PROBE_MAX_BYTES: usize = 16 * 1024;
+
+/// Returns true if the whole blob is a single brotli stream decoding to at
+/// most `BROTLI_PROBE_MAX_BYTES` of non-empty output. Empty output is
+/// rejected as the one-byte empty stream (e.g. `x'06'`) is too likely to be
+/// plain data.
+#[cfg(feature = "brotli")]
+fn is_brotli(blob: &[u8]) -> bool {
+    use std::io::Read;
+    let decodes = |input: &[u8]| {
+        let mut out = Vec::new();
+        brotli::Decompressor::new(input, 4096)
+            .take(BROTLI_PROBE_MAX_BYTES as u64 + 1)
+            .read_to_end(&mut out)
+            .is_ok()
+            && !out.is_empty()
+            && out.len() <= BROTLI_PROBE_MAX_BYTES
+    };
+    // The last byte of a stream holds its final bits, so dropping it has to
+    // break decoding; if it doesn't, the stream ended before the blob did.
+    !blob.is_empty()
+        && blob.len() <= BROTLI_PROBE_MAX_BYTES
+        && decodes(blob)
+        && !decodes(&blob[..blob.len() - 1])
+}
+
+#[cfg(not(feature = "brotli"))]
+fn is_brotli(_blob: &[u8]) -> bool {
+    false
+}
+
+/// Upper bound on the output of the decompression functions when no explicit
+/// `max_bytes` is given, so a tiny blob can't expand into gigabytes.
+const DECOMPRESS_DEFAULT_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Returns the optional `max_bytes` argument of the decompression functions.
+fn max_bytes_arg(ctx: &Context, idx: usize) -> anyhow::Result<u64> {
+    if ctx.len() <= idx {
+        return Ok(DECOMPRESS_DEFAULT_MAX_BYTES);
+    }
+    let n = ctx.get::<i64>(idx)?;
+    u64::try_from(n).map_err(|_| anyhow::anyhow!("max_bytes must not be negative, got {}", n))
+}
+
+/// Reads a decompressing reader to the end, failing once the output would
+/// exceed `max_bytes` instead of buffering all of it first.
+fn read_at_most(reader: impl std::io::Read, max_bytes: u64) -> anyhow::Result<Vec<u8>> {
+    read_at_most_with_hint(reader, max_bytes, 0)
+}
+
+/// Like `read_at_most`, reserving room for `size_hint` bytes up front. The
+/// hint usually comes from the compressed data, so it is capped at
+/// `max_bytes`, a failed reservation falls back to growing the output, and
+/// output of a different length is still handled.
+fn read_at_most_with_hint(
+    reader: impl std::io::Read,
+    max_bytes: u64,
+    size_hint: u64,
+) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read;
+    // The extra byte lets the final read see the end of the stream without
+    // growing the buffer.
+    let capacity = size_hint.min(max_bytes).saturating_add(1);
+    let mut out = Vec::new();
+    // A reservation that can't be satisfied is left to the reads below.
+    let _ = out.try_reserve_exact(usize::try_from(capacity).unwrap_or(0));
+    reader
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut out)?;
+    if out.len() as u64 > max_bytes {
+        anyhow::bail!("decompressed output exceeds {} bytes", max_bytes);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use pretty_assertions::assert_eq;
+
+    use rusqlite::Connection;
+
+    fn open_db() -> anyhow::Result<Connection> {
+        let db = Connection::open_in_memory().context("open in-memory db")?;
+        setup(&db).context("setup db")?;
+        Ok(db)
+    }
+
+    fn query<T: rusqlite::types::FromSql>(db: &Connection, sql: &str) -> anyhow::Result<T> {
+        Ok(db.query_row(sql, [], |r| r.get::<usize, T>(0))?)
+    }
+
+    fn query_err(db: &Connection, sql: &str) -> String {
+        match query::<Value>(db, sql) {
+            Ok(v) => panic!("expected {:?} to fail, got {:?}", sql, v),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn sanity() -> anyhow::Result<()> {
+        open_db()?;
+        Ok(())
+    }
+
+    #[test]
+    fn base58_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!("", query::<String>(&db, "select base58_encode(x'')")?);
+        assert_eq!(
+            "GSy",
+            query::<String>(&db, "select base58_encode(x'cafe')")?
+        );
+        assert_eq!(
+            "11GSy",
+            query::<String>(&db, "select base58_encode(x'0000cafe')")?
+        );
+        assert_eq!(
+            "0000CAFE",
+            query::<String>(&db, "select hex(base58_decode('11GSy'))")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select base58_encode(null)")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select base58_decode(null)")?
+        );
+        assert!(query_err(&db, "select base58_decode('G0y')").contains("invalid base58"));
+        Ok(())
+    }
+
+    #[test]
+    fn base58check_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Address of the Bitcoin genesis block coinbase output.
+        assert_eq!(
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            query::<String>(
+                &db,
+                "select base58check_encode(x'0062e907b15cbf27d5425399ebf6f0fb50ebb88f18')"
+            )?
+        );
+        assert_eq!(
+            "0062e907b15cbf27d5425399ebf6f0fb50ebb88f18",
+            query::<String>(
+                &db,
+                "select blob_to_hex(base58check_decode('1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa'))"
+            )?
+        );
+        assert!(query_err(
+            &db,
+            "select base58check_decode('1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb')"
+        )
+        .contains("checksum mismatch"));
+        assert!(query_err(&db, "select base58check_decode('GSy')").contains("too short"));
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select base58check_decode(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn base64_encode_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!("", query::<String>(&db, "select base64_encode(x'')")?);
+        assert_eq!(
+            "yv4=",
+            query::<String>(&db, "select base64_encode(x'cafe')")?
+        );
+        assert_eq!(
+            "+/8=",
+            query::<String>(&db, "select base64_encode(x'fbff', 'std')")?
+        );
+        assert_eq!(
+            "+/8",
+            query::<String>(&db, "select base64_encode(x'fbff', 'std_nopad')")?
+        );
+        assert_eq!(
+            "-_8=",
+            query::<String>(&db, "select base64_encode(x'fbff', 'urlsafe')")?
+        );
+        assert_eq!(
+            "-_8",
+            query::<String>(&db, "select base64_encode(x'fbff', 'urlsafe_nopad')")?
+        );
+        assert_eq!(
+            "9PbKU9ViEYabFwXOKXJrrXpn0w6+ACplRQsTrbsFpmk=",
+            query::<String>(
+                &db,
+                "select base64_encode(blob_from_hex('f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669'))"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select base64_encode(null)")?
+        );
+        assert!(query_err(&db, "select base64_encode(x'cafe', 'base32')")
+            .contains("unknown base64 mode"));
+        Ok(())
+    }
+
+    #[test]
+    fn base64_decode_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(base64_decode('yv4='))")?
+        );
+        assert_eq!(
+            "FBFF",
+            query::<String>(&db, "select hex(base64_decode('-_8=', 'urlsafe'))")?
+        );
+        assert_eq!(
+            "FBFF",
+            query::<String>(&db, "select hex(base64_decode('-_8', 'urlsafe'))")?
+        );
+        assert_eq!(
+            "FBFF",
+            query::<String>(&db, "select hex(base64_decode('+/8', 'std_nopad'))")?
+        );
+        assert_eq!(
+            "f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669",
+            query::<String>(
+                &db,
+                "select blob_to_hex(base64_decode(base64_encode(blob_from_hex('f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669'), 'urlsafe_nopad'), 'urlsafe'))"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select base64_decode(null)")?
+        );
+        assert!(query_err(&db, "select base64_decode('yv4')").contains("invalid base64"));
+        assert!(query_err(&db, "select base64_decode('yv*=')")
+            .contains("invalid base64 character '*' at offset 2"));
+        assert!(query_err(&db, "select base64_decode('-_8=')")
+            .contains("invalid base64 character '-' at offset 0"));
+        Ok(())
+    }
+
+    #[test]
+    fn null_propagates() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for sql in &[
+            "select base58_encode(null)",
+            "select base58_decode(null)",
+            "select base58check_encode(null)",
+            "select base58check_decode(null)",
+            "select base64_encode(null)",
+            "select base64_encode(x'cafe', null)",
+            "select base64_decode(null)",
+            "select bech32m_encode(null, x'cafe')",
+            "select bech32m_encode('xch', null)",
+            "select bech32m_decode(null)",
+            "select blob_from_hex(null)",
+            "select blob_to_hex(null)",
+            "select blob_to_hex(x'cafe', null)",
+            "select chia_amount_int(null)",
+            "select chia_amount_uint(null)",
+            "select chia_fullblock_json(null)",
+            "select sha256sum(null)",
+            "select zstd_decompress_blob(null)",
+        ] {
+            assert_eq!(Value::Null, query::<Value>(&db, sql)?, "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn chia_coin_json_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let coin = "blob_concat(zeroblob(31), x'01', \
+                    x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669', \
+                    chia_amount_blob('18446744073709551615'))";
+        assert_eq!(
+            "{\"parent_coin_info\":\
+             \"0x0000000000000000000000000000000000000000000000000000000000000001\",\
+             \"puzzle_hash\":\
+             \"0xf4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669\",\
+             \"amount\":18446744073709551615}",
+            query::<String>(&db, &format!("select chia_coin_json({})", coin))?
+        );
+        assert_eq!(
+            1_750_000_000_000,
+            query::<i64>(
+                &db,
+                "select json_extract(chia_coin_json(blob_concat(zeroblob(64), \
+                 chia_amount_blob(1750000000000))), '$.amount')"
+            )?
+        );
+        assert!(query_err(&db, "select chia_coin_json(zeroblob(71))")
+            .contains("failed to parse Coin from 71-byte blob"));
+        assert_eq!(
+            "trailing bytes after Coin (72 of 73 bytes used)",
+            query_err(&db, "select chia_coin_json(zeroblob(73))")
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select chia_coin_json(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_fullblock_json_reports_truncated_blob() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // An empty list of finished sub-slots followed by a cut-off reward
+        // chain block.
+        let err = query_err(&db, "select chia_fullblock_json(x'000000000000')");
+        assert!(
+            err.contains("failed to parse FullBlock from 6-byte blob"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_decompress_blob_streams_large_blobs() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // 50 MB of patterned data, a stand-in for an archived block.
+        let data: Vec<u8> = (0..50_000_000u32)
+            .map(|i| ((i % 65_521) ^ (i >> 16)) as u8)
+            .collect();
+        let compressed = zstd::stream::encode_all(&data[..], 1)?;
+        assert!(compressed.len() < data.len() / 10);
+        let decompressed: Vec<u8> = db.query_row(
+            "select zstd_decompress_blob(?)",
+            rusqlite::params![compressed],
+            |r| r.get(0),
+        )?;
+        assert!(data == decompressed, "round trip mismatch");
+
+        // With the size from the frame header, the output is allocated once
+        // at its final size rather than grown to up to twice of it.
+        let decoder = zstd::stream::read::Decoder::new(&compressed[..])?;
+        let hint = zstd::zstd_safe::get_frame_content_size(&compressed)
+            .ok()
+            .flatten();
+        assert_eq!(Some(data.len() as u64), hint);
+        let out = read_at_most_with_hint(decoder, DECOMPRESS_DEFAULT_MAX_BYTES, data.len() as u64)?;
+        assert!(out.capacity() <= data.len() + 1, "{}", out.capacity());
+        // A lying header neither reserves past max_bytes nor breaks decoding.
+        let decoder = zstd::stream::read::Decoder::new(&compressed[..])?;
+        let out = read_at_most_with_hint(decoder, 60_000_000, u64::MAX)?;
+        assert!(data == out);
+        let decoder = zstd::stream::read::Decoder::new(&compressed[..])?;
+        assert!(read_at_most_with_hint(decoder, 1_000_000, u64::MAX).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_decompress_blob_distrusts_frame_headers() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // A single-segment frame claiming 256 MiB of content, holding a raw
+        // block of 3 bytes.
+        let forged = [
+            &[0x28, 0xb5, 0x2f, 0xfd, 0xe0][..],
+            &(256u64 << 20).to_le_bytes(),
+            &[0x19, 0x00, 0x00],
+            b"abc",
+        ]
+        .concat();
+        assert_eq!(
+            256 << 20,
+            db.query_row(
+                "select zstd_frame_content_size(?)",
+                rusqlite::params![forged],
+                |r| r.get::<usize, i64>(0)
+            )?
+        );
+        assert_eq!(forged.len() as u64 * 64, zstd_size_hint(&forged));
+        // Even with a huge max_bytes, decoding fails or yields the 3 bytes
+        // actually there, instead of reserving what the header claims.
+        match db.query_row(
+            "select zstd_decompress_blob(?, 1 << 40)",
+            rusqlite::params![forged],
+            |r| r.get::<usize, Vec<u8>>(0),
+        ) {
+            Ok(out) => assert_eq!(b"abc".to_vec(), out),
+            Err(e) => assert!(!e.to_string().contains("exceeds"), "{}", e),
+        }
+        // A reservation that can't be satisfied falls back to growing.
+        let out = read_at_most_with_hint(&b"abc"[..], u64::MAX, u64::MAX >> 1)?;
+        assert_eq!(b"abc".to_vec(), out);
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_decompress_blob_respects_max_bytes() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // 16 MiB of zeros compresses to a few hundred bytes.
+        let compressed = zstd::stream::encode_all(&vec![0u8; 16 << 20][..], 3)?;
+        assert!(compressed.len() < 4096);
+        let err = db
+            .query_row(
+                "select zstd_decompress_blob(?, 1024)",
+                rusqlite::params![compressed],
+                |r| r.get::<usize, Vec<u8>>(0),
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("decompressed output exceeds 1024 bytes"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            16 << 20,
+            db.query_row(
+                "select length(zstd_decompress_blob(?))",
+                rusqlite::params![compressed],
+                |r| r.get::<usize, i64>(0),
+            )?
+        );
+        assert_eq!(
+            16 << 20,
+            db.query_row(
+                "select length(zstd_decompress_blob(?, 16 * 1024 * 1024))",
+                rusqlite::params![compressed],
+                |r| r.get::<usize, i64>(0),
+            )?
+        );
+        assert!(query_err(&db, "select zstd_decompress_blob(x'', -1)")
+            .contains("max_bytes must not be negative"));
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "xch1jlgazv".to_string(),
+            db.query_row("select bech32m_encode('xch', x'')", [], |r| r
+                .get::<usize, String>(0))?
+        );
+        assert_eq!(
+            "xch1etlqusgk05".to_string(),
+            db.query_row("select bech32m_encode('xch', x'cafe')", [], |r| r
+                .get::<usize, String>(0))?
+        );
+        assert_eq!("xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4".to_string(),
+                   db.query_row("select bech32m_encode('xch', x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669')",
+                                [],
+                                |r| r.get::<usize, String>(0))?);
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_defaults_to_xch() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let puzzle_hash = "x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669'";
+        for sql in &[
+            format!("select bech32m_encode({})", puzzle_hash),
+            format!("select bech32m_encode('xch', {})", puzzle_hash),
+            format!("select bech32m_try_encode({})", puzzle_hash),
+        ] {
+            assert_eq!(
+                "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4",
+                query::<String>(&db, sql)?,
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            "xch1etlqusgk05",
+            query::<String>(&db, "select bech32m_encode(x'cafe')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32m_encode(null)")?
+        );
+        // A lone argument is the data, not the HRP.
+        assert_eq!(
+            "invalid hex character 'x' at position 0",
+            query_err(&db, "select bech32m_encode('xch')")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_accepts_hex_text() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table puzzle_hashes (puzzle_hash text);
+             insert into puzzle_hashes values
+                 ('f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669'),
+                 ('0xcafe'),
+                 ('');",
+        )?;
+        assert_eq!(
+            3,
+            query::<i64>(
+                &db,
+                "select count(*) from puzzle_hashes
+                 where bech32m_encode('xch', puzzle_hash)
+                     = bech32m_encode('xch', blob_from_hex(puzzle_hash))
+                   and bech32m_encode(puzzle_hash)
+                     = bech32m_encode(blob_from_hex(puzzle_hash))
+                   and bech32_encode('xch', puzzle_hash)
+                     = bech32_encode('xch', blob_from_hex(puzzle_hash))"
+            )?
+        );
+        assert_eq!(
+            "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4",
+            query::<String>(
+                &db,
+                "select bech32m_encode('xch', puzzle_hash) from puzzle_hashes where rowid = 1"
+            )?
+        );
+        assert_eq!(
+            "odd-length hex string (3 chars)",
+            query_err(&db, "select bech32m_encode('xch', 'caf')")
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32m_try_encode('xch', 'not hex')")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_decode_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "".to_string(),
+            db.query_row("select hex(bech32m_decode('xch1jlgazv'))", [], |r| r
+                .get::<usize, String>(0))?
+        );
+        assert_eq!(
+            "CAFE".to_string(),
+            db.query_row("select hex(bech32m_decode('xch1etlqusgk05'))", [], |r| {
+                r.get::<usize, String>(0)
+            })?
+        );
+        assert_eq!("F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669".to_string(),
+                   db.query_row("select hex(bech32m_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'))",
+                                [],
+                                |r| r.get::<usize, String>(0))?);
+        Ok(())
+    }
+
+    #[test]
+    fn address_to_puzzle_hash_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let address = "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4";
+        for sql in &[
+            format!("select hex(address_to_puzzle_hash('{}'))", address),
+            format!("select hex(address_to_puzzle_hash('{}', 'xch'))", address),
+            format!("select hex(address_to_puzzle_hash(upper('{}')))", address),
+        ] {
+            assert_eq!(
+                "F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669",
+                query::<String>(&db, sql)?
+            );
+        }
+        for (sql, expected) in &[
+            (
+                "select address_to_puzzle_hash('xch1etlqusgk05')".to_string(),
+                "address must decode to a 32-byte puzzle hash, got 2 bytes",
+            ),
+            (
+                "select address_to_puzzle_hash('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5slz59ch')"
+                    .to_string(),
+                "expected bech32m checksum, found bech32",
+            ),
+            (
+                format!("select address_to_puzzle_hash('{}', 'txch')", address),
+                "expected HRP 'txch', got 'xch'",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select address_to_puzzle_hash(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_address_valid_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let address = "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4";
+        let testnet = "txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux";
+        for (args, expected) in &[
+            (format!("'{}'", address), 1),
+            (format!("upper('{}')", address), 1),
+            (format!("'{}'", testnet), 1),
+            (format!("'{}', 'xch'", address), 1),
+            (format!("'{}', 'XCH'", address), 1),
+            (format!("'{}', 'xch'", testnet), 0),
+            (format!("'{}', 'txch'", address), 0),
+            (format!("'{}', 1", address), 0),
+            // Right length but another HRP, and a plain bech32 checksum.
+            (
+                "'tb17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5spc3k0l'".to_string(),
+                0,
+            ),
+            (
+                "'xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5slz59ch'".to_string(),
+                0,
+            ),
+            // Two bytes, a bad checksum, garbage and a blob.
+            ("'xch1etlqusgk05'".to_string(), 0),
+            (format!("'{}'", address.replace("fa4", "fa5")), 0),
+            ("'not an address'".to_string(), 0),
+            ("''".to_string(), 0),
+            (format!("cast('{}' as blob)", address), 0),
+        ] {
+            let sql = format!("select chia_address_valid({})", args);
+            assert_eq!(*expected, query::<i64>(&db, &sql)?, "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select chia_address_valid(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_bech32_kind_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Offers are compressed spend bundles, often longer than the strings
+        // bech32m_decode accepts.
+        let long_offer = format!("offer1{}py65zk", "q".repeat(3200));
+        // As long as, and a bit longer than, the longest accepted string.
+        let longest_offer = format!("offer1{}wkysk3", "q".repeat(1048564));
+        let too_long_offer = format!("offer1{}kr5xp2", "q".repeat(1048572));
+        for (s, expected) in &[
+            (
+                "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4",
+                "address",
+            ),
+            (
+                "TXCH17NMV5574VGGCDXCHQH8ZJUNT44AX05CWHCQZ5E29PVF6MWC95E5S8ERLUX",
+                "address",
+            ),
+            (
+                "offer1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqvkdt6",
+                "offer",
+            ),
+            (long_offer.as_str(), "offer"),
+            (longest_offer.as_str(), "offer"),
+            (too_long_offer.as_str(), "unknown"),
+            // Wrong length, checksum variant, HRP or checksum.
+            ("xch1etlqusgk05", "unknown"),
+            (
+                "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5slz59ch",
+                "unknown",
+            ),
+            (
+                "tb17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5spc3k0l",
+                "unknown",
+            ),
+            (
+                "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa5",
+                "unknown",
+            ),
+            ("hello world", "unknown"),
+            ("", "unknown"),
+        ] {
+            assert_eq!(
+                *expected,
+                db.query_row("select chia_bech32_kind(?)", [s], |r| r
+                    .get::<usize, String>(0))?,
+                "{}",
+                s
+            );
+        }
+        assert_eq!(
+            "unknown",
+            query::<String>(&db, "select chia_bech32_kind(x'cafe')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select chia_bech32_kind(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_address_network_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (address, expected) in &[
+            (
+                "'xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'",
+                Some("mainnet"),
+            ),
+            (
+                "upper('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4')",
+                Some("mainnet"),
+            ),
+            (
+                "'txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux'",
+                Some("testnet"),
+            ),
+            // Not a Chia HRP, too short, not bech32m, not bech32 at all.
+            (
+                "'tb17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5spc3k0l'",
+                None,
+            ),
+            ("'xch1etlqusgk05'", None),
+            (
+                "'xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5slz59ch'",
+                None,
+            ),
+            ("'mainnet'", None),
+            ("x'cafe'", None),
+            ("null", None),
+        ] {
+            let sql = format!("select chia_address_network({})", address);
+            assert_eq!(
+                expected.map(String::from),
+                query::<Option<String>>(&db, &sql)?,
+                "{}",
+                sql
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn puzzle_hash_to_address_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let address = "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4";
+        assert_eq!(
+            address,
+            query::<String>(
+                &db,
+                &format!(
+                    "select puzzle_hash_to_address(address_to_puzzle_hash('{}'), 'xch')",
+                    address
+                )
+            )?
+        );
+        assert_eq!(
+            "txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux",
+            query::<String>(
+                &db,
+                &format!(
+                    "select puzzle_hash_to_address(address_to_puzzle_hash('{}'), 'TXCH')",
+                    address
+                )
+            )?
+        );
+        // A hex string works in place of the blob.
+        assert_eq!(
+            address,
+            query::<String>(
+                &db,
+                &format!(
+                    "select puzzle_hash_to_address(blob_to_hex(address_to_puzzle_hash('{}')), 'xch')",
+                    address
+                )
+            )?
+        );
+        assert_eq!(
+            "puzzle hash must be 32 bytes long, got 2 bytes",
+            query_err(&db, "select puzzle_hash_to_address('cafe', 'xch')")
+        );
+        for (sql, expected) in &[
+            (
+                "select puzzle_hash_to_address(x'cafe', 'xch')",
+                "puzzle hash must be 32 bytes long, got 2 bytes",
+            ),
+            (
+                "select puzzle_hash_to_address(zeroblob(33), 'xch')",
+                "puzzle hash must be 32 bytes long, got 33 bytes",
+            ),
+            (
+                "select puzzle_hash_to_address(zeroblob(32), '')",
+                "invalid bech32 HRP '': must not be empty",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select puzzle_hash_to_address(null, 'xch')")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_decode_checks_expected_len() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let address = "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4";
+        for sql in &[
+            format!("select hex(bech32m_decode('{}', 32))", address),
+            format!("select hex(bech32m_decode('{}', 'xch', 32))", address),
+            format!("select hex(bech32m_decode('{}', 32, 'xch'))", address),
+            format!("select hex(bech32m_try_decode('{}', 32))", address),
+        ] {
+            assert_eq!(
+                "F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669",
+                query::<String>(&db, sql)?,
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(bech32m_decode('xch1etlqusgk05', 2))")?
+        );
+        for sql in &[
+            "select bech32m_decode('xch1etlqusgk05', 32)",
+            "select bech32m_decode('xch1etlqusgk05', 'xch', 32)",
+            "select bech32_decode('xch1etlqusgk05', 32)",
+        ] {
+            assert_eq!(
+                "expected 32 decoded bytes, got 2 bytes",
+                query_err(&db, sql),
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            "expected TEXT HRP or INTEGER length argument, got REAL",
+            query_err(&db, "select bech32m_decode('xch1etlqusgk05', 2.0)")
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select bech32m_try_decode('xch1etlqusgk05', 32)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_decode_rejects_bech32() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert!(query_err(&db, "select bech32m_decode('xch1etlqfvc62k')")
+            .contains("expected bech32m checksum, found bech32"));
+        assert!(
+            query_err(&db, "select bech32m_decode('xch1etlqfvc62k', 'xch')")
+                .contains("expected bech32m checksum, found bech32")
+        );
+        // A puzzle hash encoded with a plain bech32 checksum used to decode
+        // to the same bytes as the real address.
+        let bech32_address = "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5slz59ch";
+        assert!(
+            query_err(&db, &format!("select bech32m_decode('{}')", bech32_address))
+                .contains("expected bech32m checksum, found bech32")
+        );
+        assert_eq!(
+            "F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669",
+            query::<String>(
+                &db,
+                &format!("select hex(bech32_decode('{}'))", bech32_address)
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_caches_repeated_values() -> anyhow::Result<()> {
+        let cached = open_db()?;
+        let uncached = open_db()?;
+        assert_eq!(
+            0,
+            query::<i64>(&uncached, "select bech32m_encode_cache_size(0)")?
+        );
+        let sql = "with recursive n(i) as (select 1 union all select i + 1 from n where i < 10000)
+                   select a, count(*) from (select bech32m_encode('xch', cast(i % 3 as blob)) as a from n)
+                   group by a order by a";
+        let mut results = Vec::new();
+        for db in &[cached, uncached] {
+            let mut stmt = db.prepare(sql)?;
+            let rows = stmt
+                .query_map([], |r| {
+                    Ok((r.get::<usize, String>(0)?, r.get::<usize, i64>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            results.push(rows);
+        }
+        assert_eq!(3, results[0].len());
+        assert_eq!(10000, results[0].iter().map(|(_, n)| n).sum::<i64>());
+        assert_eq!(results[0], results[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_cache_size_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            4096,
+            query::<i64>(&db, "select bech32m_encode_cache_size()")?
+        );
+        let encoded = query::<String>(&db, "select bech32m_encode('xch', x'cafe')")?;
+        for size in &[0, 1, 4096] {
+            assert_eq!(
+                *size,
+                query::<i64>(&db, &format!("select bech32m_encode_cache_size({})", size))?
+            );
+            assert_eq!(
+                *size,
+                query::<i64>(&db, "select bech32m_encode_cache_size()")?
+            );
+            for _ in 0..2 {
+                assert_eq!(
+                    encoded,
+                    query::<String>(&db, "select bech32m_encode('xch', x'cafe')")?
+                );
+            }
+        }
+        // Other connections keep their own cache.
+        query::<i64>(&db, "select bech32m_encode_cache_size(0)")?;
+        assert_eq!(
+            4096,
+            query::<i64>(&open_db()?, "select bech32m_encode_cache_size()")?
+        );
+        assert_eq!(
+            "value must not be negative, got -1",
+            query_err(&db, "select bech32m_encode_cache_size(-1)")
+        );
+        assert_eq!(
+            "cache size must be at most 1048576, got 1048577",
+            query_err(&db, "select bech32m_encode_cache_size(1048577)")
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select bech32m_encode_cache_size(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_cache_keeps_values_apart() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // A tiny cache forces evictions.
+        assert_eq!(3, query::<i64>(&db, "select bech32m_encode_cache_size(3)")?);
+        let mismatches = query::<i64>(
+            &db,
+            "with recursive n(i) as (select 0 union all select i + 1 from n where i < 2000)
+             select count(*) from n
+             where bech32m_decode(bech32m_encode('xch', cast(i % 7 as blob))) != cast(i % 7 as blob)
+                or bech32m_decode(bech32m_encode('xch', cast(i as blob)), 'xch') != cast(i as blob)
+                or bech32m_hrp(bech32m_encode('txch', cast(i as blob))) != 'txch'
+                or bech32_variant(bech32m_encode('xch', cast(i as blob), 'bech32')) != 'bech32'",
+        )?;
+        assert_eq!(0, mismatches);
+        // The same payload under different HRPs or variants must not collide.
+        assert_eq!(
+            3,
+            query::<i64>(
+                &db,
+                "select count(distinct a) from (
+                    select bech32m_encode('xch', x'cafe') as a
+                    union all select bech32m_encode('txch', x'cafe')
+                    union all select bech32m_encode('xch', x'cafe', 'bech32')
+                    union all select bech32m_encode('xch', x'cafe')
+                 )"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_handles_long_payloads() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for n in &[64, 128] {
+            let blob = [vec![0; *n], vec![0xff]].concat();
+            let (encoded, decoded) = db.query_row(
+                "select bech32m_encode('xch', ?1), bech32m_decode(bech32m_encode('xch', ?1)) = ?1",
+                rusqlite::params![blob],
+                |r| Ok((r.get::<usize, String>(0)?, r.get::<usize, bool>(1)?)),
+            )?;
+            assert!(encoded.len() > 90, "{}", encoded);
+            assert!(decoded);
+        }
+        assert!(
+            query_err(&db, "select bech32m_encode('xch', zeroblob(2000))")
+                .contains("at most 2048 are allowed")
+        );
+        assert!(query_err(
+            &db,
+            "select bech32m_decode('xch1' || replace(hex(zeroblob(1500)), '0', 'q'))"
+        )
+        .contains("bech32 string too long (3004 chars, at most 2048)"));
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_decode_rejects_mixed_case() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(bech32m_decode('XCH1ETLQUSGK05'))")?
+        );
+        assert!(query_err(&db, "select bech32m_decode('xch1etlqusGK05')")
+            .contains("mixed case bech32 string"));
+        // The checksum of the lowercased string is valid, so accepting mixed
+        // case would hide copy-paste corruption. All decoding functions share
+        // the check, wherever the case changes.
+        let address = "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4";
+        for mixed in &[
+            "Xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4",
+            "xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfA4",
+            "XCH17NMV5574VGGCDXCHQH8ZJUNT44AX05CWHCQZ5E29PVF6MWC95E5S27yfa4",
+        ] {
+            assert_eq!(address, mixed.to_ascii_lowercase());
+            for f in &["bech32m_decode", "bech32_decode", "address_to_puzzle_hash"] {
+                let sql = format!("select {}('{}')", f, mixed);
+                assert_eq!("mixed case bech32 string", query_err(&db, &sql), "{}", sql);
+            }
+            for (f, expected) in &[
+                ("bech32m_try_decode", None),
+                ("chia_address_valid", Some("0")),
+                ("chia_bech32_kind", Some("unknown")),
+            ] {
+                let sql = format!("select cast({}('{}') as text)", f, mixed);
+                assert_eq!(
+                    expected.map(String::from),
+                    query::<Option<String>>(&db, &sql)?,
+                    "{}",
+                    sql
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_try_decode_returns_null_on_errors() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            Some("F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669".to_string()),
+            query::<Option<String>>(
+                &db,
+                "select hex(bech32m_try_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'))"
+            )?
+        );
+        for sql in &[
+            // Truncated.
+            "select bech32m_try_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yf')",
+            // 'b' is not part of the bech32 charset.
+            "select bech32m_try_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfab')",
+            "select bech32m_try_decode('')",
+            "select bech32m_try_decode('xch1etlqfvc62k')",
+            "select bech32m_try_decode('xch1etlqusgk05', 'txch')",
+            "select bech32m_try_decode(x'cafe')",
+        ] {
+            assert_eq!(None, query::<Option<Vec<u8>>>(&db, sql)?, "{}", sql);
+        }
+        let valid = query::<i64>(
+            &db,
+            "select count(*) from (
+                select 'xch1etlqusgk05' as addr union all select 'xch1etlqusgk0' union all select 'garbage'
+             ) where bech32m_try_decode(addr) is not null",
+        )?;
+        assert_eq!(1, valid);
+        Ok(())
+    }
+
+    #[test]
+    fn try_bech32m_decode_is_an_alias() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            query::<Vec<u8>>(
+                &db,
+                "select bech32m_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4')"
+            )?,
+            query::<Vec<u8>>(
+                &db,
+                "select try_bech32m_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4')"
+            )?
+        );
+        for sql in &[
+            // Bad checksum, plain bech32 checksum, not bech32 at all.
+            "select try_bech32m_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa5')",
+            "select try_bech32m_decode('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5slz59ch')",
+            "select try_bech32m_decode('not an address')",
+            "select try_bech32m_decode(42)",
+        ] {
+            assert_eq!(None, query::<Option<Vec<u8>>>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            "CAFE",
+            query::<String>(
+                &db,
+                "select hex(try_bech32m_decode('xch1etlqusgk05', 'xch', 2))"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_try_encode_returns_null_on_errors() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            Some("xch1etlqusgk05".to_string()),
+            query::<Option<String>>(&db, "select bech32m_try_encode('xch', x'cafe')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32m_try_encode('', x'cafe')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32m_try_encode('xch', x'cafe', 'base32')")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32_decode_accepts_both_variants() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(bech32_decode('xch1etlqfvc62k'))")?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(bech32_decode('xch1etlqusgk05'))")?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(bech32_decode('xch1etlqfvc62k', 'xch'))")?
+        );
+        assert!(
+            query_err(&db, "select bech32_decode('xch1etlqfvc62k', 'txch')")
+                .contains("expected HRP 'txch', got 'xch'")
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select bech32_decode(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_hrp_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "xch",
+            query::<String>(
+                &db,
+                "select bech32m_hrp('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4')"
+            )?
+        );
+        assert_eq!(
+            "txch",
+            query::<String>(
+                &db,
+                "select bech32m_hrp('txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux')"
+            )?
+        );
+        assert_eq!(
+            "xch",
+            query::<String>(
+                &db,
+                "select bech32m_hrp('XCH17NMV5574VGGCDXCHQH8ZJUNT44AX05CWHCQZ5E29PVF6MWC95E5S27YFA4')"
+            )?
+        );
+        assert!(query::<String>(
+            &db,
+            "select bech32m_hrp('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5S27YFA4')"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_hrp_and_decode_split_addresses() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table addresses (address text);
+             insert into addresses values
+                 ('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'),
+                 ('txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux');",
+        )?;
+        let mut stmt = db.prepare(
+            "select bech32m_hrp(address), blob_to_hex(bech32m_decode(address))
+             from addresses order by rowid",
+        )?;
+        let rows = stmt
+            .query_map([], |r| {
+                Ok((r.get::<usize, String>(0)?, r.get::<usize, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let puzzle_hash = "f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669";
+        assert_eq!(
+            vec![
+                ("xch".to_string(), puzzle_hash.to_string()),
+                ("txch".to_string(), puzzle_hash.to_string()),
+            ],
+            rows
+        );
+        // So mainnet and testnet addresses of the same puzzle hash can be
+        // told apart.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select count(*) from addresses where bech32m_hrp(address) = 'txch'"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_address_collation_ignores_case() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table wallets (address text collate CHIA_ADDRESS primary key, name text);
+             create table payments (address text, amount integer);
+             insert into wallets values
+                 ('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4', 'alice'),
+                 ('xch1etlqusgk05', 'bob');
+             insert into payments values
+                 ('XCH17NMV5574VGGCDXCHQH8ZJUNT44AX05CWHCQZ5E29PVF6MWC95E5S27YFA4', 1),
+                 ('xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4', 2),
+                 ('XCH1ETLQUSGK05', 4);",
+        )?;
+        assert_eq!(
+            "alice:3,bob:4",
+            query::<String>(
+                &db,
+                "select group_concat(name || ':' || total) from (
+                     select name, sum(amount) as total
+                     from wallets join payments using (address)
+                     group by name order by name)"
+            )?
+        );
+        // Without the collation, the uppercase addresses don't match.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select count(*) from payments
+                 where address = 'xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'"
+            )?
+        );
+        assert_eq!(
+            2,
+            query::<i64>(
+                &db,
+                "select count(*) from payments
+                 where address = 'xch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s27yfa4'
+                     collate CHIA_ADDRESS"
+            )?
+        );
+        // The primary key treats differently cased addresses as duplicates.
+        assert!(db
+            .execute("insert into wallets values ('XCH1ETLQUSGK05', 'carol')", [])
+            .is_err());
+        // Anything collates, valid address or not.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select 'Not An Address' = 'not an address' collate CHIA_ADDRESS"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_decode_checks_expected_hrp() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669",
+            query::<String>(
+                &db,
+                "select hex(bech32m_decode('txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux', 'txch'))"
+            )?
+        );
+        assert_eq!(
+            "F4F6CA53D56211869B1705CE29726BAD7A67D30EBE002A65450B13ADBB05A669",
+            query::<String>(
+                &db,
+                "select hex(bech32m_decode('XCH17NMV5574VGGCDXCHQH8ZJUNT44AX05CWHCQZ5E29PVF6MWC95E5S27YFA4', 'xch'))"
+            )?
+        );
+        assert!(query_err(
+            &db,
+            "select bech32m_decode('txch17nmv5574vggcdxchqh8zjunt44ax05cwhcqz5e29pvf6mwc95e5s8erlux', 'xch')"
+        )
+        .contains("expected HRP 'xch', got 'txch'"));
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_validates_hrp() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert!(query_err(&db, "select bech32m_encode('', x'cafe')")
+            .contains("invalid bech32 HRP '': must not be empty"));
+        assert!(query_err(&db, "select bech32m_encode('x ch', x'cafe')")
+            .contains("invalid bech32 HRP 'x ch': characters must be printable ASCII"));
+        assert!(query_err(&db, "select bech32m_encode('xCh', x'cafe')")
+            .contains("invalid bech32 HRP 'xCh': must not be mixed case"));
+        assert!(query_err(
+            &db,
+            "select bech32m_encode(replace(hex(zeroblob(42)), '0', 'x'), x'cafe')"
+        )
+        .contains("must be at most 83 characters long"));
+        // Uppercase HRPs are valid and get normalized to lowercase.
+        assert_eq!(
+            "xch1etlqusgk05",
+            query::<String>(&db, "select bech32m_encode('XCH', x'cafe')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32m_encode(null, x'cafe')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32m_encode('xch', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32m_encode_selects_variant() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "xch1etlqusgk05",
+            query::<String>(&db, "select bech32m_encode('xch', x'cafe', 'bech32m')")?
+        );
+        assert_eq!(
+            "xch1etlqfvc62k",
+            query::<String>(&db, "select bech32m_encode('xch', x'cafe', 'bech32')")?
+        );
+        assert!(
+            query_err(&db, "select bech32m_encode('xch', x'cafe', 'base32')")
+                .contains("unknown bech32 variant 'base32'")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32_encode_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let bech32 = query::<String>(&db, "select bech32_encode('xch', x'cafe')")?;
+        let bech32m = query::<String>(&db, "select bech32m_encode('xch', x'cafe')")?;
+        assert_eq!("xch1etlqfvc62k", bech32);
+        assert_eq!("xch1etlqusgk05", bech32m);
+        // Same data, different checksums.
+        assert_eq!(bech32[..8], bech32m[..8]);
+        assert_ne!(bech32[8..], bech32m[8..]);
+        assert_eq!(
+            bech32,
+            query::<String>(&db, "select bech32m_encode('xch', x'cafe', 'bech32')")?
+        );
+        assert_eq!(
+            vec![0xca, 0xfe],
+            query::<Vec<u8>>(
+                &db,
+                "select bech32_decode(bech32_encode('XCH', x'cafe'), 'xch')"
+            )?
+        );
+        assert_eq!(
+            "expected bech32m checksum, found bech32",
+            query_err(&db, "select bech32m_decode(bech32_encode('xch', x'cafe'))")
+        );
+        assert!(query_err(&db, "select bech32_encode('x ch', x'cafe')")
+            .contains("invalid bech32 HRP 'x ch'"));
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32_encode('xch', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bech32_variant_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "bech32m",
+            query::<String>(&db, "select bech32_variant('xch1etlqusgk05')")?
+        );
+        assert_eq!(
+            "bech32",
+            query::<String>(&db, "select bech32_variant('xch1etlqfvc62k')")?
+        );
+        for variant in &["bech32", "bech32m"] {
+            let sql = format!(
+                "select bech32_variant(bech32m_encode('xch', x'cafe', '{0}')) = '{0}'
+                    and bech32_decode(bech32m_encode('xch', x'cafe', '{0}')) = x'cafe'",
+                variant
+            );
+            assert_eq!(1, query::<i64>(&db, &sql)?, "{}", variant);
+        }
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select bech32_variant(null)")?
+        );
+        assert!(query::<String>(&db, "select bech32_variant('xch1etlqusgk06')").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn blob_from_hex_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "CAFE".to_string(),
+            db.query_row("select hex(blob_from_hex('cafe'))", [], |r| r
+                .get::<usize, String>(0))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blob_from_hex_is_lenient() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(blob_from_hex('0xCAFE'))")?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(blob_from_hex('0Xcafe'))")?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select blob_from_hex('0xcafe') = blob_from_hex('cafe')"
+            )?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(blob_from_hex('ca fe' || char(10)))")?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(blob_from_hex('cAfE'))")?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(blob_from_hex('CA FE'))")?
+        );
+        assert_eq!(
+            "CAFE0123",
+            query::<String>(
+                &db,
+                "select hex(blob_from_hex(' CA' || char(10) || 'fe' || char(13, 10, 9) || '01 23 '))"
+            )?
+        );
+        assert_eq!("", query::<String>(&db, "select hex(blob_from_hex(''))")?);
+        assert_eq!("", query::<String>(&db, "select hex(blob_from_hex('0x'))")?);
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select blob_from_hex(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blob_from_hex_reports_errors() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert!(query_err(&db, "select blob_from_hex('0xcafe0')")
+            .contains("odd-length hex string (5 chars)"));
+        assert!(query_err(&db, "select blob_from_hex('abc')")
+            .contains("odd-length hex string (3 chars)"));
+        assert!(query_err(&db, "select blob_from_hex('zz')")
+            .contains("invalid hex character 'z' at position 0"));
+        assert!(query_err(&db, "select blob_from_hex('cazz')")
+            .contains("invalid hex character 'z' at position 2"));
+        assert!(query_err(&db, "select blob_from_hex('ca\u{0444}e')")
+            .contains("invalid hex character '\u{0444}' at position 2"));
+        assert!(query_err(&db, "select blob_from_hex('\u{1F600}')").contains("at position 0"));
+        Ok(())
+    }
+
+    #[test]
+    fn hex_roundtrips_large_blobs_quickly() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch("create table t as select randomblob(5242880) as b")?;
+        let start = std::time::Instant::now();
+        assert_eq!(
+            1,
+            query::<i64>(&db, "select blob_from_hex(blob_to_hex(b)) = b from t")?
+        );
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(5),
+            "5 MiB hex round trip took {:?}",
+            start.elapsed()
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select blob_from_hex('0x' || blob_to_hex(b, 'upper')) = b from t"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blob_to_hex_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!("cafe", query::<String>(&db, "select blob_to_hex(x'CAFE')")?);
+        assert_eq!("", query::<String>(&db, "select blob_to_hex(x'')")?);
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select blob_to_hex(x'cafe', 'upper')")?
+        );
+        assert_eq!(
+            "0xcafe",
+            query::<String>(&db, "select blob_to_hex(x'cafe', '0x')")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select blob_to_hex(null)")?
+        );
+        assert_eq!(
+            "0xcafe",
+            query::<String>(&db, "select blob_to_hex(x'cafe', true)")?
+        );
+        assert_eq!("0x", query::<String>(&db, "select blob_to_hex(x'', 1)")?);
+        assert_eq!(
+            "cafe",
+            query::<String>(&db, "select blob_to_hex(x'cafe', false)")?
+        );
+        assert!(query::<String>(&db, "select blob_to_hex('cafe')").is_err());
+        assert!(query::<String>(&db, "select blob_to_hex(x'cafe', 'octal')").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn blob_to_hex_roundtrips_with_blob_from_hex() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669",
+            query::<String>(
+                &db,
+                "select blob_to_hex(blob_from_hex('f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669'))"
+            )?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select blob_from_hex(blob_to_hex(x'00ff10')) = x'00ff10'"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_int_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            5509699999997u64,
+            db.query_row("select chia_amount_int(x'00000502D3B618FD')", [], |r| r
+                .get::<usize, u64>(
+                0
+            ))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_int_pads_short_blobs() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(0, query::<i64>(&db, "select chia_amount_int(x'')")?);
+        assert_eq!(5, query::<i64>(&db, "select chia_amount_int(x'05')")?);
+        assert_eq!(
+            5509699999997,
+            query::<i64>(&db, "select chia_amount_int(x'0502D3B618FD')")?
+        );
+        assert_eq!(255, query::<i64>(&db, "select chia_amount_uint(x'ff')")?);
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_int_rejects_long_blobs() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert!(
+            query_err(&db, "select chia_amount_int(x'000000000000000001')")
+                .contains("at most 8 bytes, got 9 bytes")
+        );
+        assert!(
+            query_err(&db, "select chia_amount_uint(x'000000000000000001')")
+                .contains("at most 8 bytes, got 9 bytes")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_int_survives_odd_lengths() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create temp table coins(amount blob);
+             insert into coins values
+               (x'00000502D3B618FD'), (x'000502D3B618FD'), (x'0000000502D3B618FD');",
+        )?;
+        assert_eq!(
+            5509699999997,
+            query::<i64>(&db, "select chia_amount_int(x'000502D3B618FD')")?
+        );
+        assert_eq!(
+            "amount blob must be at most 8 bytes, got 9 bytes",
+            query_err(&db, "select chia_amount_int(x'0000000502D3B618FD')")
+        );
+        // One bad row fails the query with an error, not the process.
+        assert_eq!(
+            "amount blob must be at most 8 bytes, got 9 bytes",
+            query_err(&db, "select sum(chia_amount_int(amount)) from coins")
+        );
+        assert_eq!(
+            2 * 5509699999997,
+            query::<i64>(
+                &db,
+                "select sum(chia_amount_int(amount)) from coins where length(amount) <= 8"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_int_rejects_amounts_above_i64_max() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            i64::MAX,
+            query::<i64>(&db, "select chia_amount_int(x'7FFFFFFFFFFFFFFF')")?
+        );
+        for (blob, mojos) in &[
+            ("8000000000000000", "9223372036854775808"),
+            ("FFFFFFFFFFFFFFFF", "18446744073709551615"),
+        ] {
+            assert_eq!(
+                format!(
+                    "amount {} does not fit into a signed 64-bit integer \
+                     (use chia_amount_text or chia_amount_uint)",
+                    mojos
+                ),
+                query_err(&db, &format!("select chia_amount_int(x'{}')", blob))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_text_is_exact() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create temp table coins(amount blob);
+             insert into coins values
+               (x'FFFFFFFFFFFFFFFF'), (x'0000000000000001'), (x'8000000000000000'),
+               (x'7FFFFFFFFFFFFFFF'), (x'00000502D3B618FD');",
+        )?;
+        for (blob, expected) in &[
+            ("x''", "0"),
+            ("x'05'", "5"),
+            ("x'7FFFFFFFFFFFFFFF'", "9223372036854775807"),
+            ("x'8000000000000000'", "9223372036854775808"),
+            ("x'FFFFFFFFFFFFFFFF'", "18446744073709551615"),
+            ("'00000502d3b618fd'", "5509699999997"),
+        ] {
+            let sql = format!("select chia_amount_text({})", blob);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select chia_amount_text(null)")?
+        );
+        // 8-byte amount blobs compare like the amounts, so they are what to
+        // sort and filter by; the text is for display.
+        assert_eq!(
+            "1,5509699999997,9223372036854775807,9223372036854775808,18446744073709551615",
+            query::<String>(
+                &db,
+                "select group_concat(mojos) from \
+                   (select chia_amount_text(amount) as mojos from coins order by amount)"
+            )?
+        );
+        assert_eq!(
+            "9223372036854775808,18446744073709551615",
+            query::<String>(
+                &db,
+                "select group_concat(mojos) from \
+                   (select chia_amount_text(amount) as mojos from coins \
+                    where amount > x'7FFFFFFFFFFFFFFF' order by amount)"
+            )?
+        );
+        // Filtering by the integer value fails loudly instead of treating
+        // large amounts as negative.
+        assert!(query_err(
+            &db,
+            "select count(*) from coins where chia_amount_int(amount) > 0"
+        )
+        .contains("does not fit into a signed 64-bit integer"));
+        Ok(())
+    }
+
+    #[test]
+    fn sha256sum_hashes_text_as_utf8() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            (
+                "select blob_to_hex(sha256sum('cafe'))",
+                "a860b858265b22dad3aaf1165cfc2936daf1d3d86e0b7b77e3cc07f59f96858f",
+            ),
+            (
+                "select blob_to_hex(sha256sum('héllo'))",
+                "3c48591d8d098a4538f5e013dfcf406e948eac4d3277b10bf614e295d6068179",
+            ),
+            (
+                "select blob_to_hex(sha256sum(blob_from_hex('cafe')))",
+                "03346f0e7990de2423a3bca5335bf92cdc0bd14bef2206b87c63f18a1e996c52",
+            ),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        assert!(query_err(&db, "select sha256sum(42)")
+            .contains("expected BLOB or TEXT argument, got INTEGER"));
+        Ok(())
+    }
+
+    #[test]
+    fn merkle_set_root_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Items are the SHA256 digests of 0, 1, ... as 4-byte integers.
+        let root = |n: i64, order: &str| {
+            db.query_row(
+                &format!(
+                    "with recursive t(i) as (select 0 union all select i + 1 from t where i + 1 < ?)
+                     select blob_to_hex(merkle_set_root(item)) from (
+                         select sha256sum(uint_to_be_blob(i, 4)) as item from t
+                         where i < ? order by {}
+                     )",
+                    order
+                ),
+                rusqlite::params![n.max(1), n],
+                |r| r.get::<usize, String>(0),
+            )
+        };
+        for (n, expected) in &[
+            (
+                0,
+                "0000000000000000000000000000000000000000000000000000000000000000",
+            ),
+            (
+                1,
+                "6f133666a9e177ca9532f405c317b3dd953fd5aff1ac68ceef153fe011d2f975",
+            ),
+            (
+                2,
+                "7b55132993730fa336473ba87a5f32989bc841904f8c1585abb5dc92cc87b630",
+            ),
+            (
+                3,
+                "c3d0b38c92176ac0c00507a70d9cd007916d8cd566cf6e2139e03747044009e2",
+            ),
+            (
+                4,
+                "0dd5cb0d0b569b3290206cd46b06f93ac8c40fa3c280a47144cdfa9cb2c07d1e",
+            ),
+            (
+                100,
+                "80e37677101aa5979154c689d078d64c5c5c805c88fab3bf6aace090a2623c9e",
+            ),
+            (
+                1000,
+                "2e37c9eda5417e84e7ee0101f2ab5b54b25b5e1230eb62069f6a3a0aff065267",
+            ),
+        ] {
+            // The order items are fed in doesn't matter.
+            for order in &["i", "i desc", "item"] {
+                assert_eq!(*expected, root(*n, order)?, "{} items by {}", n, order);
+            }
+        }
+
+        // Duplicates and NULLs don't change the root.
+        assert_eq!(
+            "7b55132993730fa336473ba87a5f32989bc841904f8c1585abb5dc92cc87b630",
+            query::<String>(
+                &db,
+                "select blob_to_hex(merkle_set_root(item)) from (
+                     select sha256sum(uint_to_be_blob(value, 4)) as item
+                     from (select 0 as value union all select 1 union all select 0)
+                     union all select null
+                 )"
+            )?
+        );
+        assert_eq!(
+            "merkle set items must be 32 bytes long, got 2 bytes",
+            query_err(&db, "select merkle_set_root(x'cafe')")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn merkle_set_verify_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Proofs for sets of the SHA256 digests of 0, 1, ... as 4-byte
+        // integers, as in `merkle_set_root_works`.
+        db.execute_batch(
+            "create table proof (root blob, item blob, proof blob, included integer, valid integer);
+             insert into proof values
+                 -- Empty set, exclusion.
+                 (x'0000000000000000000000000000000000000000000000000000000000000000',
+                  x'df3f619804a92fdb4057192dc43dd748ea778adc52bc498ce80524c014b81119',
+                  x'00', 0, 1),
+                 -- {0}, inclusion of 0.
+                 (x'6f133666a9e177ca9532f405c317b3dd953fd5aff1ac68ceef153fe011d2f975',
+                  x'df3f619804a92fdb4057192dc43dd748ea778adc52bc498ce80524c014b81119',
+                  x'01df3f619804a92fdb4057192dc43dd748ea778adc52bc498ce80524c014b81119',
+                  1, 1),
+                 -- {0, 1, 2}, inclusion of 1.
+                 (x'c3d0b38c92176ac0c00507a70d9cd007916d8cd566cf6e2139e03747044009e2',
+                  x'b40711a88c7039756fb8a73827eabe2c0fe5a0346ca7e0a104adc0fc764f528d',
+                  x'0201433ebf5bc03dffa38536673207a21281612cef5faa9bc7a4d5b9be2fdb12cf1a02\
+                   01b40711a88c7039756fb8a73827eabe2c0fe5a0346ca7e0a104adc0fc764f528d\
+                   01df3f619804a92fdb4057192dc43dd748ea778adc52bc498ce80524c014b81119',
+                  1, 1),
+                 -- The same proof doesn't prove exclusion.
+                 (x'c3d0b38c92176ac0c00507a70d9cd007916d8cd566cf6e2139e03747044009e2',
+                  x'b40711a88c7039756fb8a73827eabe2c0fe5a0346ca7e0a104adc0fc764f528d',
+                  x'0201433ebf5bc03dffa38536673207a21281612cef5faa9bc7a4d5b9be2fdb12cf1a02\
+                   01b40711a88c7039756fb8a73827eabe2c0fe5a0346ca7e0a104adc0fc764f528d\
+                   01df3f619804a92fdb4057192dc43dd748ea778adc52bc498ce80524c014b81119',
+                  0, 0),
+                 -- Same, with a bit flipped in the proven item.
+                 (x'c3d0b38c92176ac0c00507a70d9cd007916d8cd566cf6e2139e03747044009e2',
+                  x'b40711a88c7039756fb8a73827eabe2c0fe5a0346ca7e0a104adc0fc764f528d',
+                  x'0201433ebf5bc03dffa38536673207a21281612cef5faa9bc7a4d5b9be2fdb12cf1a02\
+                   01b40711a88d7039756fb8a73827eabe2c0fe5a0346ca7e0a104adc0fc764f528d\
+                   01df3f619804a92fdb4057192dc43dd748ea778adc52bc498ce80524c014b81119',
+                  1, 0),
+                 -- {0, 1, 2, 3}, exclusion of 9 next to a truncated subtree.
+                 (x'0dd5cb0d0b569b3290206cd46b06f93ac8c40fa3c280a47144cdfa9cb2c07d1e',
+                  x'3b28a6c3766c6295adad976d349705ef49393fbfdebe4facb5862d2cdfa285d3',
+                  x'0201433ebf5bc03dffa38536673207a21281612cef5faa9bc7a4d5b9be2fdb12cf1a03\
+                   37984509bca8d334f44504485b0511795b7a413bd3e389ff517ee7bdd97c515a',
+                  0, 1),
+                 -- Same, cut short.
+                 (x'0dd5cb0d0b569b3290206cd46b06f93ac8c40fa3c280a47144cdfa9cb2c07d1e',
+                  x'3b28a6c3766c6295adad976d349705ef49393fbfdebe4facb5862d2cdfa285d3',
+                  x'0201433ebf5bc03dffa38536673207a21281612cef5faa9bc7a4d5b9be2fdb12cf1a03\
+                   37984509bca8d334f44504485b0511795b7a413bd3e389ff517ee7bdd97c51',
+                  0, 0),
+                 -- Same, with trailing garbage.
+                 (x'0dd5cb0d0b569b3290206cd46b06f93ac8c40fa3c280a47144cdfa9cb2c07d1e',
+                  x'3b28a6c3766c6295adad976d349705ef49393fbfdebe4facb5862d2cdfa285d3',
+                  x'0201433ebf5bc03dffa38536673207a21281612cef5faa9bc7a4d5b9be2fdb12cf1a03\
+                   37984509bca8d334f44504485b0511795b7a413bd3e389ff517ee7bdd97c515a00',
+                  0, 0),
+                 -- Not a proof at all.
+                 (x'0dd5cb0d0b569b3290206cd46b06f93ac8c40fa3c280a47144cdfa9cb2c07d1e',
+                  x'3b28a6c3766c6295adad976d349705ef49393fbfdebe4facb5862d2cdfa285d3',
+                  x'cafe', 0, 0);",
+        )?;
+        let mut stmt = db.prepare(
+            "select rowid, merkle_set_verify(root, item, proof, included), valid from proof",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let (rowid, valid, expected) = (
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            );
+            assert_eq!(expected, valid, "row {}", rowid);
+        }
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select merkle_set_verify(root, item, proof) from proof where rowid = 3"
+            )?
+        );
+        // Deeply nested garbage is rejected without recursing too deep.
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                &format!(
+                    "select merkle_set_verify(zeroblob(32), zeroblob(32), x'{}', 0)",
+                    "02".repeat(100_000)
+                )
+            )?
+        );
+        assert_eq!(
+            "merkle set root must be 32 bytes long, got 2 bytes",
+            query_err(
+                &db,
+                "select merkle_set_verify(x'cafe', zeroblob(32), x'00')"
+            )
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(
+                &db,
+                "select merkle_set_verify(zeroblob(32), zeroblob(32), null)"
+            )?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli_decompress_blob_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Written by the reference encoder (libbrotlienc) at quality 11.
+        for (frame, expected) in &[
+            ("8b0080cafe03", &b"\xca\xfe"[..]),
+            ("1b2700f8a5c3c6d0d2441901806d00", &b"chia".repeat(10)[..]),
+            ("06", &b""[..]),
+        ] {
+            let sql = format!("select brotli_decompress_blob(x'{}')", frame);
+            assert_eq!(expected.to_vec(), query::<Vec<u8>>(&db, &sql)?, "{}", sql);
+        }
+        assert!(query_err(
+            &db,
+            "select brotli_decompress_blob(x'1b2700f8a5c3c6d0d2441901806d00', 39)"
+        )
+        .contains("decompressed output exceeds 39 bytes"));
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select brotli_decompress_blob(null)")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli_compress_blob_roundtrips() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for sql in &[
+            "select brotli_decompress_blob(brotli_compress_blob(x'cafe'))",
+            "select brotli_decompress_blob(brotli_compress_blob(x'cafe', 0))",
+            "select brotli_decompress_blob(brotli_compress_blob(x'cafe', 5))",
+        ] {
+            assert_eq!(vec![0xca, 0xfe], query::<Vec<u8>>(&db, sql)?, "{}", sql);
+        }
+        let data = "chia".repeat(100_000);
+        let compressed = db.query_row(
+            "select brotli_compress_blob(?)",
+            rusqlite::params![data],
+            |r| r.get::<usize, Vec<u8>>(0),
+        )?;
+        assert!(compressed.len() < 1024);
+        assert_eq!(
+            data.as_bytes(),
+            &db.query_row(
+                "select brotli_decompress_blob(?)",
+                rusqlite::params![compressed],
+                |r| r.get::<usize, Vec<u8>>(0),
+            )?[..]
+        );
+        assert_eq!(
+            "brotli quality must be between 0 and 11, got 12",
+            query_err(&db, "select brotli_compress_blob(x'cafe', 12)")
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_compress_blob_roundtrips() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for sql in &[
+            "select gzip_decompress_blob(gzip_compress_blob(x'cafe'))",
+            "select gzip_decompress_blob(gzip_compress_blob(x'cafe', 0))",
+            "select gzip_decompress_blob(gzip_compress_blob(x'cafe', 9))",
+        ] {
+            assert_eq!(vec![0xca, 0xfe], query::<Vec<u8>>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            b"chia".to_vec(),
+            query::<Vec<u8>>(
+                &db,
+                "select gzip_decompress_blob(gzip_compress_blob('chia'))"
+            )?
+        );
+        assert_eq!(
+            "1F8B",
+            query::<String>(&db, "select hex(substr(gzip_compress_blob(x'cafe'), 1, 2))")?
+        );
+        let data = vec![0u8; 1 << 20];
+        let compressed = db.query_row(
+            "select gzip_compress_blob(?)",
+            rusqlite::params![data],
+            |r| r.get::<usize, Vec<u8>>(0),
+        )?;
+        assert!(compressed.len() < 4096);
+        let mut decompressed = Vec::new();
+        std::io::Read::read_to_end(
+            &mut flate2::read::GzDecoder::new(&compressed[..]),
+            &mut decompressed,
+        )?;
+        assert_eq!(data, decompressed);
+        for level in &[-1, 10] {
+            assert_eq!(
+                format!("gzip level must be between 0 and 9, got {}", level),
+                query_err(
+                    &db,
+                    &format!("select gzip_compress_blob(x'cafe', {})", level)
+                )
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_decompress_blob_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Written by Python's gzip.compress() with mtime=0.
+        let gzip = "1f8b08000000000002033bf50f0020f2c92a02000000";
+        for sql in &[
+            format!("select gzip_decompress_blob(x'{}')", gzip),
+            format!("select gzip_decompress_blob('{}')", gzip),
+            format!("select gzip_decompress_blob(x'{}', 2)", gzip),
+            // The same data, deflated without framing.
+            "select inflate_blob(x'3bf50f00')".to_string(),
+        ] {
+            assert_eq!(vec![0xca, 0xfe], query::<Vec<u8>>(&db, sql)?, "{}", sql);
+        }
+        // Concatenated gzip files decompress to the concatenated contents.
+        assert_eq!(
+            b"\xca\xfechia".to_vec(),
+            query::<Vec<u8>>(
+                &db,
+                &format!(
+                    "select gzip_decompress_blob(x'{}1f8b08000000000002034bcec84c0400499c99c004000000')",
+                    gzip
+                )
+            )?
+        );
+        assert!(
+            query_err(&db, &format!("select gzip_decompress_blob(x'{}', 1)", gzip))
+                .contains("decompressed output exceeds 1 bytes")
+        );
+        assert!(query_err(
+            &db,
+            "select gzip_decompress_blob(x'28b52ffd0458110000cafe23ae5cb0')"
+        )
+        .contains("invalid gzip header"));
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select gzip_decompress_blob(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compression_format_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            (
+                "select compression_format(x'28b52ffd2002110000cafe')",
+                "zstd",
+            ),
+            (
+                "select compression_format(x'1f8b08000000000002033bf50f0020f2c92a02000000')",
+                "gzip",
+            ),
+            (
+                "select compression_format(x'04224d1860408202000080cafe00000000')",
+                "lz4",
+            ),
+            ("select compression_format(x'cafe')", "none"),
+            ("select compression_format(x'')", "none"),
+            // Only the magic bytes are checked.
+            ("select compression_format(x'28b52ffd')", "zstd"),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            "zstd",
+            query::<String>(
+                &db,
+                "select compression_format(zstd_compress_blob(x'cafe'))"
+            )?
+        );
+        assert_eq!(
+            "expected BLOB argument, got TEXT",
+            query_err(&db, "select compression_format('28b52ffd')")
+        );
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select compression_format(null)")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn compression_format_detects_brotli() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select compression_format(x'8b0080cafe03')", "brotli"),
+            (
+                "select compression_format(brotli_compress_blob(randomblob(1000)))",
+                "brotli",
+            ),
+            // Missing the last byte of the stream.
+            ("select compression_format(x'8b0080cafe')", "none"),
+            // A complete stream followed by more data.
+            ("select compression_format(x'8b0080cafe03cafe')", "none"),
+            // The empty stream.
+            ("select compression_format(x'06')", "none"),
+            // Too much output to be worth probing.
+            (
+                "select compression_format(brotli_compress_blob(zeroblob(1000000)))",
+                "none",
+            ),
+            ("select compression_format(x'cafe')", "none"),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_decompress_blob_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Written by the reference library (LZ4F_compressFrame, default
+        // preferences); the first frame stores its block uncompressed.
+        for (frame, expected) in &[
+            ("04224d1860408202000080cafe00000000", &b"\xca\xfe"[..]),
+            (
+                "04224d186040820e0000004f6368696104000c50616368696100000000",
+                &b"chia".repeat(10)[..],
+            ),
+            ("04224d1860408200000000", &b""[..]),
+        ] {
+            let sql = format!("select lz4_decompress_blob(x'{}')", frame);
+            assert_eq!(expected.to_vec(), query::<Vec<u8>>(&db, &sql)?, "{}", sql);
+        }
+        assert_eq!(
+            b"\xca\xfe".to_vec(),
+            query::<Vec<u8>>(
+                &db,
+                "select lz4_decompress_blob('04224d1860408202000080cafe00000000')"
+            )?
+        );
+        assert!(query_err(
+            &db,
+            "select lz4_decompress_blob(x'04224d186040820e0000004f6368696104000c50616368696100000000', 39)"
+        )
+        .contains("decompressed output exceeds 39 bytes"));
+        // A zstd frame is no LZ4 frame.
+        assert!(
+            !query_err(&db, "select lz4_decompress_blob(x'28b52ffd2002110000cafe')").is_empty()
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select lz4_decompress_blob(null)")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_compress_blob_roundtrips() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for sql in &[
+            "select lz4_decompress_blob(lz4_compress_blob(x'cafe'))",
+            "select lz4_decompress_blob(lz4_compress_blob(blob_from_hex('cafe')))",
+        ] {
+            assert_eq!(vec![0xca, 0xfe], query::<Vec<u8>>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select length(lz4_decompress_blob(lz4_compress_blob(x'')))"
+            )?
+        );
+        let data = "chia".repeat(100_000);
+        let compressed = db.query_row(
+            "select lz4_compress_blob(?)",
+            rusqlite::params![data],
+            |r| r.get::<usize, Vec<u8>>(0),
+        )?;
+        assert!(compressed.len() < data.len() / 100);
+        assert_eq!(b"\x04\x22\x4d\x18", &compressed[..4]);
+        assert_eq!(
+            data.as_bytes(),
+            &db.query_row(
+                "select lz4_decompress_blob(?)",
+                rusqlite::params![compressed],
+                |r| r.get::<usize, Vec<u8>>(0),
+            )?[..]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn hmac_sha256_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            // RFC 4231, test cases 1, 2 and 6
+            (
+                "select blob_to_hex(hmac_sha256(x'0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b', 'Hi There'))",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                "select blob_to_hex(hmac_sha256('Jefe', 'what do ya want for nothing?'))",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                "select blob_to_hex(hmac_sha256(
+                     blob_from_hex(replace(hex(zeroblob(131)), '00', 'aa')),
+                     'Test Using Larger Than Block-Size Key - Hash Key First'))",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                "select blob_to_hex(hmac_sha256(x'', x''))",
+                "b613679a0814d9ec772f95d778c35fc5ff1697c493715653c6c712144292c5ad",
+            ),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hmac_sha256_passes_rfc4231() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Builds a blob of `n` copies of the hex byte `b`.
+        let repeat = |b: &str, n: usize| format!("x'{}'", b.repeat(n));
+        for (key, data, expected) in &[
+            (
+                repeat("aa", 20),
+                repeat("dd", 50),
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                "x'0102030405060708090a0b0c0d0e0f10111213141516171819'".to_string(),
+                repeat("cd", 50),
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            // Test case 5 only specifies the first 128 bits.
+            (
+                repeat("0c", 20),
+                "'Test With Truncation'".to_string(),
+                "a3b6167473100ee06e0c796c2955552b",
+            ),
+            (
+                repeat("aa", 131),
+                "'This is a test using a larger than block-size key and a larger than block-size \
+                 data. The key needs to be hashed before being used by the HMAC algorithm.'"
+                    .to_string(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ] {
+            let mac = query::<String>(
+                &db,
+                &format!("select blob_to_hex(hmac_sha256({}, {}))", key, data),
+            )?;
+            assert!(mac.starts_with(expected), "{} {}: {}", key, data, mac);
+            assert_eq!(64, mac.len());
+        }
+        // Keys longer than the 64-byte block are hashed first.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select hmac_sha256(zeroblob(65), 'm') = hmac_sha256(sha256sum(zeroblob(65)), 'm')
+                    and hmac_sha256(zeroblob(64), 'm') != hmac_sha256(sha256sum(zeroblob(64)), 'm')"
+            )?
+        );
+        for sql in &[
+            "select hmac_sha256(null, 'm')",
+            "select hmac_sha256('k', null)",
+        ] {
+            assert_eq!(None, query::<Option<Vec<u8>>>(&db, sql)?, "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sha224_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7",
+            query::<String>(&db, "select blob_to_hex(sha224('abc'))")?
+        );
+        assert_eq!(
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f",
+            query::<String>(&db, "select blob_to_hex(sha224(x''))")?
+        );
+        // Not a truncated SHA256: the initial hash values differ.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select length(sha224(x'cafe')) = 28
+                    and sha224(x'cafe') != blob_slice(sha256sum(x'cafe'), 0, 28)"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256_agg_works() -> anyhow::Result<()> {
+        use sha2::Digest;
+        let db = open_db()?;
+        db.execute_batch(
+            "create table t(i integer, b blob);
+             insert into t values (2, x'fe'), (1, x'ca'), (4, null), (3, x'babe');",
+        )?;
+        let mut hasher = sha2::Sha256::new();
+        hasher.update([0xca]);
+        hasher.update([0xfe]);
+        hasher.update([0xba, 0xbe]);
+        assert_eq!(
+            hasher.finalize().to_vec(),
+            query::<Vec<u8>>(
+                &db,
+                "select sha256_agg(b) from (select b from t order by i)"
+            )?
+        );
+        assert_eq!(
+            query::<Vec<u8>>(&db, "select sha256sum(x'')")?,
+            query::<Vec<u8>>(&db, "select sha256_agg(b) from t where 0")?
+        );
+        assert!(query_err(&db, "select sha256_agg(i) from t")
+            .contains("expected BLOB or TEXT argument, got INTEGER"));
+        Ok(())
+    }
+
+    #[test]
+    fn sum_mojo_doesnt_overflow() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table coins (amount integer);
+             insert into coins values
+                 (9223372036854775807), (9223372036854775807), (2), (null);",
+        )?;
+        assert_eq!(
+            "18446744073709551616",
+            query::<String>(&db, "select sum_mojo(amount) from coins")?
+        );
+        // Whereas sum() raises an error on overflow, and total() rounds.
+        assert!(query_err(&db, "select sum(amount) from coins").contains("overflow"));
+        assert_eq!(
+            "18446744073709551616",
+            query::<String>(
+                &db,
+                "select sum_mojo(a) from (
+                     select '18446744073709551615' as a union all select 1)"
+            )?
+        );
+        assert_eq!(
+            4,
+            query::<i64>(&db, "select sum_mojo(amount) from coins where amount < 10")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(
+                &db,
+                "select sum_mojo(amount) from coins where amount is null"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select sum_mojo(amount) from coins where 0")?
+        );
+        assert_eq!(
+            "value must not be negative, got -1",
+            query_err(
+                &db,
+                "select sum_mojo(a) from (select 1 as a union all select -1)"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn running_mojo_balance_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table deltas (height integer, delta);
+             insert into deltas values
+                 (1, 1000), (2, -250), (3, null), (4, '-18446744073709551615'),
+                 (5, x'ffffffffffffffff'), (6, '18446744073709551615'), (7, -750);",
+        )?;
+        let balances = |sql: &str| -> anyhow::Result<Vec<Option<String>>> {
+            let mut stmt = db.prepare(sql)?;
+            let rows = stmt.query_map([], |r| r.get::<usize, Option<String>>(0))?;
+            Ok(rows.collect::<Result<_, _>>()?)
+        };
+        let s = |s: &str| Some(s.to_string());
+        assert_eq!(
+            vec![
+                s("1000"),
+                s("750"),
+                s("750"),
+                s("-18446744073709550865"),
+                s("750"),
+                s("18446744073709552365"),
+                s("18446744073709551615"),
+            ],
+            balances(
+                "select cast(running_mojo_balance(delta) over (order by height) as text)
+                 from deltas order by height"
+            )?
+        );
+        // Sliding frames remove values leaving the frame again; a frame of
+        // only NULL values yields NULL.
+        assert_eq!(
+            vec![
+                s("1000"),
+                s("750"),
+                s("-250"),
+                s("-18446744073709551615"),
+                s("0"),
+                s("36893488147419103230"),
+                s("18446744073709550865"),
+            ],
+            balances(
+                "select cast(running_mojo_balance(delta) over (
+                     order by height rows between 1 preceding and current row) as text)
+                 from deltas order by height"
+            )?
+        );
+        assert_eq!(
+            vec![s("-250"), None, s("-18446744073709551615")],
+            balances(
+                "select cast(running_mojo_balance(delta) over (
+                     order by height rows current row) as text)
+                 from deltas where height between 2 and 4 order by height"
+            )?
+        );
+        // Usable as plain aggregate, too.
+        assert_eq!(
+            "18446744073709551615",
+            query::<String>(&db, "select running_mojo_balance(delta) from deltas")?
+        );
+        assert_eq!(
+            "invalid amount '--1': invalid digit found in string",
+            query_err(&db, "select running_mojo_balance('--1')")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sum_mojo_mixes_blobs_and_integers() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table coins (amount);
+             insert into coins values
+                 (x'ffffffffffffffff'), (1), (x'000000e8d4a51000'), ('1000000000000'), (x'');",
+        )?;
+        assert_eq!(
+            "18446746073709551616",
+            query::<String>(&db, "select sum_mojo(amount) from coins")?
+        );
+        assert_eq!(
+            2_000_000_000_001,
+            query::<i64>(&db, "select sum_mojo(amount) from coins where rowid > 1")?
+        );
+        assert_eq!(
+            "amount blob must be at most 8 bytes, got 9 bytes",
+            query_err(&db, "select sum_mojo(x'000000000000000001')")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256_file_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let path = std::env::temp_dir().join(format!("sqlite-chia-{}.txt", std::process::id()));
+        std::fs::write(&path, "abc")?;
+        let digest = db.query_row(
+            "select blob_to_hex(sha256_file(?))",
+            rusqlite::params![path.to_str()],
+            |r| r.get::<usize, String>(0),
+        );
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            digest?
+        );
+        let err = db
+            .query_row(
+                "select sha256_file(?)",
+                rusqlite::params![path.to_str()],
+                |r| r.get::<usize, Vec<u8>>(0),
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(&format!("failed to open '{}'", path.display())),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256_prefix_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "03346f0e",
+            query::<String>(&db, "select blob_to_hex(sha256_prefix(x'cafe', 4))")?
+        );
+        assert_eq!(
+            query::<Vec<u8>>(&db, "select sha256sum(x'cafe')")?,
+            query::<Vec<u8>>(&db, "select sha256_prefix(x'cafe', 40)")?
+        );
+        assert_eq!(
+            query::<Vec<u8>>(&db, "select substr(sha256sum('abc'), 1, 4)")?,
+            query::<Vec<u8>>(&db, "select sha256_prefix('abc', 4)")?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(&db, "select length(sha256_prefix(x'', 0))")?
+        );
+        assert_eq!(
+            "prefix length must not be negative, got -1",
+            query_err(&db, "select sha256_prefix(x'cafe', -1)")
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select sha256_prefix(x'cafe', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256sum_concat_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select sha256sum_concat(x'ca', x'fe') = sha256sum(x'cafe')"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select sha256sum_concat(x'ca', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256_agg_matches_one_shot_hash() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create table coins as
+             select randomblob(32) as name, value as i from (select 1 as value union all select 2 union all select 3);",
+        )?;
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select (select sha256_agg(name) from (select name from coins order by i))
+                      = (select sha256sum(blob_concat(a.name, b.name, c.name))
+                         from coins a, coins b, coins c where a.i = 1 and b.i = 2 and c.i = 3)"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256sum_hashes_multiple_arguments() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let coin_id = query::<String>(
+            &db,
+            "select blob_to_hex(sha256sum(
+                 x'abababababababababababababababababababababababababababababababab',
+                 x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669',
+                 clvm_int_encode(1000000000000)
+             ))",
+        )?;
+        assert_eq!(
+            "0692307ba1e14997bd556f291f5412f2641dee88eef21e59f765060ca57f1eca",
+            coin_id
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select sha256sum(x'ca', 'fe', x'') = sha256sum(x'ca' || cast('fe' as blob))"
+            )?
+        );
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            query::<String>(&db, "select blob_to_hex(sha256sum())")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select sha256sum(x'ca', null, x'fe')")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn hex_text_is_accepted_for_byte_arguments() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            1_000_000_000_000,
+            query::<i64>(&db, "select chia_amount_int('000000e8d4a51000')")?
+        );
+        assert_eq!(
+            1_000_000_000_000,
+            query::<i64>(&db, "select chia_amount_uint('0x000000e8d4a51000')")?
+        );
+        assert_eq!(
+            255,
+            query::<i64>(&db, "select chia_amount_from_minimal('00ff')")?
+        );
+        assert_eq!(
+            "CAFE",
+            query::<String>(
+                &db,
+                "select hex(zstd_decompress_blob(blob_to_hex(x'28b52ffd2002110000cafe')))"
+            )?
+        );
+        // hex digits stored as a BLOB
+        assert_eq!(
+            "CAFE",
+            query::<String>(&db, "select hex(blob_from_hex(cast('cafe' as blob)))")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blob_slice_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select hex(blob_slice(x'00112233', 1, 2))", "1122"),
+            ("select hex(blob_slice(x'00112233', 1))", "112233"),
+            ("select hex(blob_slice(x'00112233', -2))", "2233"),
+            ("select hex(blob_slice(x'00112233', -3, 1))", "11"),
+            ("select hex(blob_slice(x'00112233', -10, 2))", "0011"),
+            ("select hex(blob_slice(x'00112233', 2, 100))", "2233"),
+            ("select hex(blob_slice(x'00112233', 4))", ""),
+            ("select hex(blob_slice(x'00112233', 100, 1))", ""),
+            ("select hex(blob_slice(x'00112233', 1, 0))", ""),
+            ("select hex(blob_slice(x'', 0, 1))", ""),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        assert!(query_err(&db, "select blob_slice(x'00', 0, -1)")
+            .contains("slice length must not be negative"));
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select blob_slice(x'00', null, 1)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bls_fingerprint_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            // The master public key of the first EIP-2333 test seed.
+            (
+                "select bls_fingerprint(x'a2c975348667926acf12f3eecb005044e08a7a9b7d95f30bd281b55445107367\
+                                          a2e5d0558be7943c8bd13f9a1a7036fb')",
+                331_118_975,
+            ),
+            // Hex strings as found in config.yaml work too.
+            (
+                "select bls_fingerprint('0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905\
+                                         a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb')",
+                2_093_959_050,
+            ),
+            // Not a valid point, but it has a fingerprint.
+            ("select bls_fingerprint(zeroblob(48))", 397_440_543),
+        ] {
+            assert_eq!(*expected, query::<i64>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            "public key must be 48 bytes long, got 2 bytes",
+            query_err(&db, "select bls_fingerprint(x'cafe')")
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select bls_fingerprint(null)")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_pubkey_valid_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let infinity = format!("c0{}", "00".repeat(47));
+        for (point, expected) in &[
+            // The generator, i.e. the public key of secret key 1.
+            ("97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb", 1),
+            // The master public key of the first EIP-2333 test seed.
+            ("a2c975348667926acf12f3eecb005044e08a7a9b7d95f30bd281b55445107367a2e5d0558be7943c8bd13f9a1a7036fb", 1),
+            (&infinity, 1),
+            // Infinity with a stray bit.
+            (&format!("c0{}01", "00".repeat(46)), 0),
+            // x = 1 is not on the curve.
+            (&format!("80{}01", "00".repeat(46)), 0),
+            // (0, 2) is on the curve, but not in the G1 subgroup.
+            (&format!("80{}", "00".repeat(47)), 0),
+            // Uncompressed encodings are 96 bytes long.
+            ("17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb", 0),
+            // x is larger than the field modulus.
+            ("baaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", 0),
+            ("97f1d3a731", 0),
+            ("", 0),
+        ] {
+            let sql = format!("select bls_pubkey_valid(x'{}')", point);
+            assert_eq!(*expected, query::<i64>(&db, &sql)?, "{}", sql);
+        }
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                &format!("select bls_pubkey_valid(x'{}', false)", infinity)
+            )?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(&db, "select bls_pubkey_valid(randomblob(48))")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select bls_pubkey_valid(null)")?
+        );
+        assert!(query_err(&db, "select bls_pubkey_valid(42)").contains("expected BLOB"));
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_signature_valid_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The G2 generator.
+        let generator = "93e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049\
+                         334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051\
+                         c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8";
+        let infinity = format!("c0{}", "00".repeat(95));
+        for (point, expected) in &[
+            (generator.to_string(), 1),
+            (infinity.clone(), 1),
+            // A flipped bit in x moves the point off the curve or out of the
+            // subgroup.
+            (generator.replacen("93e0", "93e1", 1), 0),
+            (generator[..190].to_string(), 0),
+            ("97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb".to_string(), 0),
+        ] {
+            let sql = format!("select bls_signature_valid(x'{}')", point);
+            assert_eq!(*expected, query::<i64>(&db, &sql)?, "{}", sql);
+        }
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                &format!("select bls_signature_valid(x'{}', 0)", infinity)
+            )?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(&db, "select bls_signature_valid(randomblob(96))")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_key_derivation_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The master key of the BIP39 test mnemonic "abandon abandon ... art"
+        // (23 times "abandon"), derived from its seed like chia does.
+        let master = "x'1109b3e2c8c9e1f150d06d1353247b22363bb35cae9b8a0b05a7cb407f5c4eb7'";
+        let hex_query = |expr: &str| -> anyhow::Result<String> {
+            query(
+                &db,
+                &format!("select lower(hex({}))", expr.replace("$m", master)),
+            )
+        };
+        assert_eq!(
+            "85d91fbd74fa98e38c07d87e22ade3244384d379d1a57e281ccd4dcf54c1b3ca\
+             6cf8dbbebee2b6d67dc00529899e5f24",
+            hex_query("bls_sk_to_pk($m)")?
+        );
+        assert_eq!(
+            2_944_534_523,
+            query::<i64>(
+                &db,
+                &format!("select bls_fingerprint(bls_sk_to_pk({}))", master)
+            )?
+        );
+        assert_eq!(
+            "44c55d0f37208cba8148fd8eb38c522005895a0dc2f29e06bc7957d4da58fc4b",
+            hex_query("bls_derive_child_sk($m, 0)")?
+        );
+        assert_eq!(
+            "4858a6921b607ee0f3fc411b1da6bac7ad332bdf54881cbb7b710fd7afa0918d",
+            hex_query("bls_derive_child_sk_unhardened($m, 0)")?
+        );
+        // Test case 0 of EIP-2333.
+        assert_eq!(
+            "2d18bd6c14e6d15bf8b5085c9b74f3daae3b03cc2014770a599d8c1539e50f8e",
+            hex_query(
+                "bls_derive_child_sk(\
+                 x'0d7359d57963ab8fbbde1852dcf553fedbc31f464d80ee7d40ae683122b45070', 0)"
+            )?
+        );
+        for (expr, expected) in &[
+            (
+                "chia_wallet_pk($m, 0)",
+                "9289946b25599e18c1a75350e2dbab812c91e5850ec3ed266056d00158a142db\
+                 820c49b122d31242694e4bc285225654",
+            ),
+            (
+                "chia_wallet_pk($m, 1, false)",
+                "a018070dfbb6823aff17d1c48d8a3b8d16cae74308fd156709c7a77baa295015\
+                 5918e8feb371e3ff1c260e52b07368db",
+            ),
+            (
+                "chia_wallet_pk($m, 0, true)",
+                "8113a23c73c892ed8a8a26dca45e1a9931ea41808f21489d06a47582fb420dea\
+                 3348dc42d1cf4acbd242813beaef8da6",
+            ),
+            // Spelled out, as done for other paths like the farmer key's.
+            (
+                "bls_sk_to_pk(bls_derive_child_sk_unhardened(bls_derive_child_sk_unhardened(\
+                 bls_derive_child_sk_unhardened(bls_derive_child_sk_unhardened(\
+                 $m, 12381), 8444), 2), 0))",
+                "9289946b25599e18c1a75350e2dbab812c91e5850ec3ed266056d00158a142db\
+                 820c49b122d31242694e4bc285225654",
+            ),
+            (
+                "bls_sk_to_pk(bls_derive_child_sk(bls_derive_child_sk(\
+                 bls_derive_child_sk(bls_derive_child_sk($m, 12381), 8444), 0), 0))",
+                "83948134a169bb263630b79c9e1d41c0f163d54ead68e57b2347c6bac407cc2a\
+                 2bebc0b3ab4b339125fd39b87a7d7a17",
+            ),
+        ] {
+            assert_eq!(*expected, hex_query(expr)?, "{}", expr);
+        }
+        // Hex strings work too.
+        assert_eq!(
+            hex_query("chia_wallet_pk($m, 0)")?,
+            hex_query(
+                "chia_wallet_pk(\
+                 '1109b3e2c8c9e1f150d06d1353247b22363bb35cae9b8a0b05a7cb407f5c4eb7', 0)"
+            )?
+        );
+        for (sql, expected) in &[
+            (
+                "select bls_sk_to_pk(x'cafe')",
+                "secret key must be 32 bytes long, got 2 bytes",
+            ),
+            (
+                "select bls_sk_to_pk(x'ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff')",
+                "secret key is not a valid BLS12-381 scalar",
+            ),
+            (
+                "select bls_derive_child_sk(zeroblob(31), 0)",
+                "secret key must be 32 bytes long, got 31 bytes",
+            ),
+            (
+                "select chia_wallet_pk(randomblob(33), 0)",
+                "secret key must be 32 bytes long, got 33 bytes",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        for index in &[-1i64, 1 << 32] {
+            assert_eq!(
+                format!(
+                    "derivation index must be between 0 and 4294967295, got {}",
+                    index
+                ),
+                query_err(
+                    &db,
+                    &format!(
+                        "select bls_derive_child_sk_unhardened({}, {})",
+                        master, index
+                    )
+                )
+            );
+        }
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select chia_wallet_pk(null, 0)")?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select bls_derive_child_sk(x'', null)")?
+        );
+
+        // Not usable from the schema unless it is trusted.
+        db.execute_batch(&format!(
+            "create view wallet as select chia_wallet_pk({}, 0) as pk;\
+             pragma trusted_schema = off;",
+            master
+        ))?;
+        assert!(query_err(&db, "select pk from wallet").contains("unsafe use of chia_wallet_pk"));
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn chia_mnemonic_to_master_sk_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let abandon = |last: &str, len: usize| {
+            let mut words = vec!["abandon"; len - 1];
+            words.push(last);
+            words.join(" ")
+        };
+        let about = abandon("about", 12);
+        let art = abandon("art", 24);
+        let seed = |sql: &str, mnemonic: &str| -> anyhow::Result<String> {
+            Ok(db.query_row(sql, rusqlite::params![mnemonic], |r| r.get(0))?)
+        };
+        // The BIP-39 test vector whose seed is EIP-2333's first test seed.
+        assert_eq!(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+             1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            seed(
+                "select lower(hex(chia_mnemonic_to_seed(?, 'TREZOR')))",
+                &about
+            )?
+        );
+        assert_eq!(
+            "408b285c123836004f4b8842c89324c1f01382450c0d439af345ba7fc49acf70\
+             5489c6fc77dbd4e3dc1dd8cc6bc9f043db8ada1e243c4a0eafb290d399480840",
+            seed("select lower(hex(chia_mnemonic_to_seed(?)))", &art)?
+        );
+        assert_eq!(
+            seed("select lower(hex(chia_mnemonic_to_seed(?)))", &art)?,
+            seed("select lower(hex(chia_mnemonic_to_seed(?, '')))", &art)?
+        );
+        for (mnemonic, passphrase, sk, fingerprint) in &[
+            (
+                &about,
+                "TREZOR",
+                "0d7359d57963ab8fbbde1852dcf553fedbc31f464d80ee7d40ae683122b45070",
+                331_118_975,
+            ),
+            (
+                &art,
+                "",
+                "1109b3e2c8c9e1f150d06d1353247b22363bb35cae9b8a0b05a7cb407f5c4eb7",
+                2_944_534_523,
+            ),
+            (
+                &about,
+                "",
+                "528ca2018585232a3212491b7e015a6b3d3d7a2b5144c2ea6ab761d8ac8a1cff",
+                630_799_605,
+            ),
+        ] {
+            let (hex_sk, fp): (String, i64) = db.query_row(
+                "select lower(hex(sk)), bls_fingerprint(bls_sk_to_pk(sk)) \
+                 from (select chia_master_sk_from_seed(chia_mnemonic_to_seed(?, ?)) as sk)",
+                rusqlite::params![mnemonic.as_str(), passphrase],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )?;
+            assert_eq!((*sk, *fingerprint), (&hex_sk[..], fp), "{}", mnemonic);
+        }
+
+        let err = |mnemonic: &str| {
+            db.query_row(
+                "select chia_mnemonic_to_seed(?)",
+                rusqlite::params![mnemonic],
+                |r| r.get::<usize, Vec<u8>>(0),
+            )
+            .unwrap_err()
+            .to_string()
+        };
+        assert_eq!(
+            "unknown mnemonic word 'abandonn' at position 3",
+            err(&art.replacen("abandon abandon abandon", "abandon abandon abandonn", 1))
+        );
+        assert_eq!("invalid mnemonic checksum", err(&abandon("abandon", 12)));
+        assert_eq!(
+            "mnemonic must have 12, 15, 18, 21 or 24 words, got 3",
+            err("abandon abandon about")
+        );
+        assert_eq!(
+            "seed must be at least 32 bytes long, got 16 bytes",
+            query_err(&db, "select chia_master_sk_from_seed(zeroblob(16))")
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select chia_mnemonic_to_seed(null, '')")?
+        );
+
+        // Like the other secret key functions, these aren't innocuous.
+        db.execute_batch(
+            "create view seed as select chia_mnemonic_to_seed('') as seed;\
+             pragma trusted_schema = off;",
+        )?;
+        assert!(query_err(&db, "select seed from seed").contains("unsafe use"));
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_public_key_derivation_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The master public key of the test mnemonic of bls_key_derivation_works.
+        let master = "x'85d91fbd74fa98e38c07d87e22ade3244384d379d1a57e281ccd4dcf54c1b3ca\
+                        6cf8dbbebee2b6d67dc00529899e5f24'";
+        let master_sk = "x'1109b3e2c8c9e1f150d06d1353247b22363bb35cae9b8a0b05a7cb407f5c4eb7'";
+        let hex_query = |expr: &str| -> anyhow::Result<String> {
+            query(
+                &db,
+                &format!(
+                    "select lower(hex({}))",
+                    expr.replace("$m", master).replace("$sk", master_sk)
+                ),
+            )
+        };
+        assert_eq!(
+            "83a0f3fdac99a8833b444b542a8c8fd88aa44907b98038ec861dda8acc48369c\
+             3e7616ed095c0507bb09c69586912cd6",
+            hex_query("bls_derive_child_pk_unhardened($m, 0)")?
+        );
+        // The first observer wallet keys.
+        for (index, expected) in [
+            "9289946b25599e18c1a75350e2dbab812c91e5850ec3ed266056d00158a142db\
+             820c49b122d31242694e4bc285225654",
+            "a018070dfbb6823aff17d1c48d8a3b8d16cae74308fd156709c7a77baa295015\
+             5918e8feb371e3ff1c260e52b07368db",
+            "b4949b261583ffd14a34b4f84390e7ccf6d6b5d9ec0a2a33ed9f4822cfb4aa22\
+             91994d4907ec77450528ac8ad3c8c290",
+            "80752300ec9869fe9cf522a4493b1f19de5d35210b3136990ae656551a49e488\
+             d9c37b880131dbf69ac61c2f4d3897e2",
+            "a796a9b24e8518bda9a50a3d9e3b6c1182127d60cf46fd1fb5b49d6b467b58d0\
+             3c19912695808a0e632390d4251ea481",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let expr = format!("chia_observer_pk($m, {})", index);
+            assert_eq!(*expected, hex_query(&expr)?, "{}", expr);
+            assert_eq!(
+                *expected,
+                hex_query(&format!("chia_wallet_pk($sk, {})", index))?
+            );
+        }
+        // Public and secret derivation agree at any index.
+        for index in &[0, 12381, 1 << 31, u32::MAX] {
+            assert_eq!(
+                hex_query(&format!(
+                    "bls_sk_to_pk(bls_derive_child_sk_unhardened($sk, {}))",
+                    index
+                ))?,
+                hex_query(&format!("bls_derive_child_pk_unhardened($m, {})", index))?
+            );
+        }
+        for (sql, expected) in &[
+            (
+                "select chia_observer_pk(x'cafe', 0)",
+                "public key must be 48 bytes long, got 2 bytes",
+            ),
+            (
+                "select bls_derive_child_pk_unhardened(zeroblob(48), 0)",
+                "public key is not a valid BLS12-381 G1 point",
+            ),
+            (
+                "select chia_observer_pk(zeroblob(32), 0)",
+                "public key must be 48 bytes long, got 32 bytes",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        for sql in &[
+            "select bls_derive_child_sk($m, 0)",
+            "select chia_wallet_pk($m, 0, true)",
+        ] {
+            let sql = sql.replace("$m", master);
+            assert!(
+                query_err(&db, &sql).contains("hardened keys can only be derived from secret keys"),
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            "derivation index must be between 0 and 4294967295, got -1",
+            query_err(&db, &format!("select chia_observer_pk({}, -1)", master))
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select chia_observer_pk(null, 0)")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn chia_synthetic_pk_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The first observer key of the test mnemonic of bls_key_derivation_works.
+        let wallet_pk = "x'9289946b25599e18c1a75350e2dbab812c91e5850ec3ed266056d00158a142db\
+                           820c49b122d31242694e4bc285225654'";
+        let g1 = "x'97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac58\
+                    6c55e83ff97a1aeffb3af00adb22c6bb'";
+        let zeros = "x'0000000000000000000000000000000000000000000000000000000000000000'";
+        for (args, expected) in [
+            (
+                wallet_pk.to_string(),
+                "a4c8f441b4118b71df6b8f0d63062df10b3c03bfd9aac5d59eae1c018f5fc1c9\
+                 1fd8c69da347eb6c4433e896a3a0601a",
+            ),
+            (
+                format!(
+                    "{}, x'711d6c4e32c92e53179b199484cf8c897542bc57f2b22582799f9d657eec4699'",
+                    wallet_pk
+                ),
+                "a4c8f441b4118b71df6b8f0d63062df10b3c03bfd9aac5d59eae1c018f5fc1c9\
+                 1fd8c69da347eb6c4433e896a3a0601a",
+            ),
+            // The offsets of these hash to negative numbers.
+            (
+                format!("{}, {}", wallet_pk, zeros),
+                "b97dce0e499382dd424e3e97992fb99f59c7a456f31cc792d2bbcabee143e7e7\
+                 9b0415f8a007f3c2b8aa4fff1f095d38",
+            ),
+            (
+                g1.to_string(),
+                "a6207f5173ec41491d9f2c1b8fff5579e13703077e0eaca8fe587669dcccf51e\
+                 9209a6b65576845ece5f7c2f3229e7e3",
+            ),
+            (
+                format!("{}, {}", g1, zeros),
+                "a2e61a3a1390bc68b176203d5b802ffabd89a82e46292b04c6293bbeb74ce774\
+                 3b1c6360edc11a1e39f49ba7fe6f7865",
+            ),
+        ]
+        .iter()
+        {
+            let sql = format!("select lower(hex(chia_synthetic_pk({})))", args);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            (
+                "select chia_synthetic_pk(zeroblob(48))",
+                "public key is not a valid BLS12-381 G1 point",
+            ),
+            (
+                "select chia_synthetic_pk(x'cafe')",
+                "public key must be 48 bytes long, got 2 bytes",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            "hidden puzzle hash must be 32 bytes long, got 2 bytes",
+            query_err(&db, &format!("select chia_synthetic_pk({}, x'cafe')", g1))
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, &format!("select chia_synthetic_pk({}, null)", g1))?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn chia_puzzle_hash_for_pk_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The synthetic key of the first observer key of the test mnemonic of
+        // bls_key_derivation_works.
+        let synthetic_pk = "x'a4c8f441b4118b71df6b8f0d63062df10b3c03bfd9aac5d59eae1c018f5fc1c9\
+                              1fd8c69da347eb6c4433e896a3a0601a'";
+        let master_pk = "x'85d91fbd74fa98e38c07d87e22ade3244384d379d1a57e281ccd4dcf54c1b3ca\
+                           6cf8dbbebee2b6d67dc00529899e5f24'";
+        for (expr, expected) in &[
+            (
+                format!("blob_to_hex(chia_puzzle_hash_for_pk({}))", synthetic_pk),
+                "c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0da545364",
+            ),
+            (
+                format!("chia_address_for_pk({}, 'xch')", synthetic_pk),
+                "xch1cpweansx6k6nudr5t5039wl63qkr4tg6e27qxmyc8quapkj52djqfl8hpn",
+            ),
+            (
+                format!("chia_address_for_pk({}, 'txch')", synthetic_pk),
+                "txch1cpweansx6k6nudr5t5039wl63qkr4tg6e27qxmyc8quapkj52djqycqpqq",
+            ),
+            // The first receive address of a watch-only wallet, all in SQL.
+            (
+                format!(
+                    "chia_address_for_pk(chia_synthetic_pk(chia_observer_pk({}, 0)), 'xch')",
+                    master_pk
+                ),
+                "xch1cpweansx6k6nudr5t5039wl63qkr4tg6e27qxmyc8quapkj52djqfl8hpn",
+            ),
+            (
+                format!(
+                    "bech32m_decode(chia_address_for_pk({0}, 'xch')) = chia_puzzle_hash_for_pk({0})",
+                    synthetic_pk
+                ),
+                "1",
+            ),
+        ] {
+            let sql = format!("select cast({} as text)", expr);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            (
+                "select chia_puzzle_hash_for_pk(zeroblob(48))",
+                "public key is not a valid BLS12-381 G1 point",
+            ),
+            (
+                "select chia_address_for_pk(x'cafe', 'xch')",
+                "public key must be 48 bytes long, got 2 bytes",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert!(query_err(
+            &db,
+            &format!("select chia_address_for_pk({}, '')", synthetic_pk)
+        )
+        .contains("invalid bech32 HRP"));
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select chia_address_for_pk(null, 'xch')")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn curry_tree_hash_matches_curried_program() -> anyhow::Result<()> {
+        // (a (q . (a 5 11)) (c (q . pk) 1)) with a 48-byte pk of 0, 1, ... 47.
+        let module = hex::decode(b"ff02ff05ff0b80")?;
+        let pk: Vec<u8> = (0..48).collect();
+        let curried = hex::decode(
+            b"ff02ffff01ff02ff05ff0b80ffff04ffff01b0000102030405060708090a0b0c0d0e0f\
+              101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2fff018080",
+        )?;
+        assert_eq!(
+            clvm::tree_hash(&curried)?,
+            clvm::curry_tree_hash(&clvm::tree_hash(&module)?, &[clvm::hash_atom(&pk)])
+        );
+        assert_eq!(
+            clvm::tree_hash(&hex::decode(b"ff02ffff01ff02ff05ff0b80ff0180")?)?,
+            clvm::curry_tree_hash(&clvm::tree_hash(&module)?, &[])
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn synthetic_offset_reduces_signed_digests() -> anyhow::Result<()> {
+        let offset = |digest: &str| -> anyhow::Result<String> {
+            let digest = <[u8; 32]>::try_from(&hex::decode(digest.as_bytes())?[..])?;
+            Ok(hex::encode(&synthetic_offset(&digest), "", false))
+        };
+        for (digest, expected) in &[
+            // 2^255 - 1, the largest positive digest.
+            (
+                "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "0c1258acd66282b7ccc627f7f65e27faac425bfd0001a40100000000fffffffe",
+            ),
+            // The group order itself.
+            (
+                "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+            ),
+            (
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+            ),
+            // -1 and -2^255.
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000",
+            ),
+            (
+                "8000000000000000000000000000000000000000000000000000000000000000",
+                "67db4ea6533afa906673b0101343b00aa77b4805fffcb7fdfffffffe00000002",
+            ),
+        ] {
+            assert_eq!(*expected, offset(digest)?, "{}", digest);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_sign_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create temp table keys as select sk, bls_sk_to_pk(sk) as pk
+             from (select bls_keygen(x'c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e\
+                                       9efa3708e53495531f09a6987599d18264c1e1c92f2cf141\
+                                       630c7a3c4ab7c81b2f001698e7463b04') as sk);",
+        )?;
+        assert_eq!(
+            "0d7359d57963ab8fbbde1852dcf553fedbc31f464d80ee7d40ae683122b45070",
+            query::<String>(&db, "select lower(hex(sk)) from keys")?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select bls_verify(pk, 'chia', bls_sign(sk, 'chia')) from keys"
+            )?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select bls_verify(pk, x'63686961', bls_sign(sk, 'chia')) from keys"
+            )?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select bls_verify(pk, 'chiA', bls_sign(sk, 'chia')) from keys"
+            )?
+        );
+        // The same signature as the library's, and deterministic.
+        let sk = chia_bls::SecretKey::from_seed(&hex::decode(
+            b"c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+              1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        )?);
+        assert_eq!(
+            chia_bls::sign(&sk, b"chia").to_bytes().to_vec(),
+            query::<Vec<u8>>(&db, "select bls_sign(sk, 'chia') from keys")?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select bls_sign(sk, 'chia') = bls_sign(sk, 'chia') from keys"
+            )?
+        );
+        for (sql, expected) in &[
+            (
+                "select bls_sign(x'cafe', 'chia')",
+                "secret key must be 32 bytes long, got 2 bytes",
+            ),
+            (
+                "select bls_sign(x'ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff', 'chia')",
+                "secret key is not a valid BLS12-381 scalar",
+            ),
+            (
+                "select bls_keygen(x'cafe')",
+                "seed must be at least 32 bytes long, got 2 bytes",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select bls_sign(sk, null) from keys")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "secp")]
+    #[test]
+    fn secp_verify_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Signatures of the SHA256 digest of 'chia'.
+        let digest = "3d4237d9383a7b6e60d1bfe551139ec2d6e5468205bf179ed381e66bed7b9788";
+        for (name, pk, sig) in &[
+            (
+                "secp256k1_verify",
+                "028208f5abf04066bad1db9d46f8bcf5a6cc11d0558ab523e7bd3c0ec08bdb782f",
+                "a44cb272a55287993dd03b4db643d29b15ddb001240eec7aa875803a2f0221b1\
+                 2b4612e5ac2cd96d4b93e53711ade2336d9c05d2ed53b8d1ee865e3fada92d60",
+            ),
+            (
+                "secp256r1_verify",
+                "026fb949d79b64b1471a9e09c76850bccbd891fe476df080f861a50b1d3e89c9a5",
+                "b4ae1d5a1b24df764be9b1303f463fa67e1a189c83dccc4a0ef8175ec9bd7607\
+                 693097782e5e51d81fb617737e3b267c240f722c0ef1a68d7e67703cc336321b",
+            ),
+        ] {
+            let verify = |pk: &str, digest: &str, sig: &str| {
+                query::<i64>(
+                    &db,
+                    &format!("select {}(x'{}', x'{}', x'{}')", name, pk, digest, sig),
+                )
+            };
+            assert_eq!(1, verify(pk, digest, sig)?, "{}", name);
+            let other_digest = format!("{}00", &digest[..62]);
+            assert_eq!(0, verify(pk, &other_digest, sig)?, "{}", name);
+            // Malformed arguments: an x coordinate beyond the field, a
+            // truncated digest, a zero signature and the wrong key prefix.
+            assert_eq!(0, verify(&format!("02{}", "ff".repeat(32)), digest, sig)?);
+            assert_eq!(0, verify(pk, &digest[..62], sig)?, "{}", name);
+            assert_eq!(0, verify(pk, digest, &"00".repeat(64))?, "{}", name);
+            assert_eq!(0, verify(&format!("04{}", &pk[2..]), digest, sig)?);
+            assert_eq!(
+                None,
+                query::<Option<i64>>(&db, &format!("select {}(null, x'', x'')", name))?
+            );
+        }
+        // The high-s twin of the secp256k1 signature.
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select secp256k1_verify(\
+                   x'028208f5abf04066bad1db9d46f8bcf5a6cc11d0558ab523e7bd3c0ec08bdb782f',\
+                   x'3d4237d9383a7b6e60d1bfe551139ec2d6e5468205bf179ed381e66bed7b9788',\
+                   x'a44cb272a55287993dd03b4db643d29b15ddb001240eec7aa875803a2f0221b1\
+                     d4b9ed1a53d32692b46c1ac8ee521dcb4d12d713c1f4e769d14c004d228d13e1')"
+            )?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_verify_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The first EIP-2333 test seed, from which chia derives keys the same
+        // way.
+        let seed = hex::decode(
+            b"c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+              1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        )?;
+        let sk = chia_bls::SecretKey::from_seed(&seed);
+        let pk = sk.public_key().to_bytes().to_vec();
+        assert_eq!(
+            "a2c975348667926acf12f3eecb005044e08a7a9b7d95f30bd281b55445107367\
+             a2e5d0558be7943c8bd13f9a1a7036fb",
+            hex::encode(&pk, "", false)
+        );
+        let sig = chia_bls::sign(&sk, b"chia").to_bytes().to_vec();
+        let mut flipped = sig.clone();
+        flipped[50] ^= 1;
+        let verify = |pk: &[u8], message: &dyn rusqlite::ToSql, sig: &[u8]| {
+            db.query_row(
+                "select bls_verify(?, ?, ?)",
+                rusqlite::params![pk, message, sig],
+                |r| r.get::<usize, i64>(0),
+            )
+        };
+        assert_eq!(1, verify(&pk, &b"chia".to_vec(), &sig)?);
+        assert_eq!(1, verify(&pk, &"chia", &sig)?);
+        assert_eq!(0, verify(&pk, &"chiA", &sig)?);
+        assert_eq!(0, verify(&pk, &"chia", &flipped)?);
+        assert_eq!(0, verify(&pk, &"chia", &sig[..95])?);
+        assert_eq!(0, verify(&pk[..47], &"chia", &sig)?);
+        // Nor does the signature for another public key.
+        let other = chia_bls::SecretKey::from_seed(&[0; 32]);
+        assert_eq!(0, verify(&other.public_key().to_bytes(), &"chia", &sig)?);
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select bls_verify(x'', null, x'')")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bls")]
+    #[test]
+    fn bls_aggregation_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute("create table spend (pk blob, msg blob, sig blob)", [])?;
+        for (i, msg) in [&b"chia"[..], b"", b"\xca\xfe"].iter().enumerate() {
+            let sk = chia_bls::SecretKey::from_seed(&[i as u8; 32]);
+            db.execute(
+                "insert into spend values (?, ?, ?)",
+                rusqlite::params![
+                    sk.public_key().to_bytes().to_vec(),
+                    msg,
+                    chia_bls::sign(&sk, msg).to_bytes().to_vec()
+                ],
+            )?;
+        }
+        let pairs =
+            "(select json_group_array(json_object('pk', hex(pk), 'msg', hex(msg))) from spend)";
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                &format!(
+                    "select bls_aggregate_verify({}, (select bls_aggregate_sigs(sig) from spend))",
+                    pairs
+                )
+            )?
+        );
+        // Leaving out any signature breaks verification.
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                &format!("select bls_aggregate_verify({}, (select bls_aggregate_sigs(sig) from spend where rowid > 1))", pairs)
+            )?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select bls_aggregate_verify('[{\"pk\": \"cafe\", \"msg\": \"\"}]', zeroblob(96))"
+            )?
+        );
+        assert!(
+            query_err(&db, "select bls_aggregate_verify('{}', zeroblob(96))")
+                .contains("expected a JSON array")
+        );
+
+        // G + G = 2G.
+        assert_eq!(
+            "a572cbea904d67468808c8eb50a9450c9721db309128012543902d0ac358a62a\
+             e28f75bb8f1c7c42c39a8c5529bf0f4e",
+            query::<String>(
+                &db,
+                "select blob_to_hex(bls_aggregate_pks(pk)) from (
+                     select x'97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb' as pk
+                     union all select null
+                     union all select x'97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb'
+                 )"
+            )?
+        );
+        // Empty groups yield the point at infinity.
+        assert_eq!(
+            format!("c0{}", "00".repeat(47)),
+            query::<String>(
+                &db,
+                "select blob_to_hex(bls_aggregate_pks(pk)) from spend where 0"
+            )?
+        );
+        assert_eq!(
+            format!("c0{}", "00".repeat(95)),
+            query::<String>(
+                &db,
+                "select blob_to_hex(bls_aggregate_sigs(sig)) from spend where 0"
+            )?
+        );
+        assert!(query_err(
+            &db,
+            "select bls_aggregate_sigs(s) from (select sig as s from spend union all select zeroblob(96))"
+        )
+        .contains("invalid BLS signature in row 4"));
+        Ok(())
+    }
+
+    #[test]
+    fn blob_bitwise_ops_work() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let a = "x'ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb'";
+        let b = "x'3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d'";
+        assert_eq!(
+            "f4b46904ca22e480c94b7ed6fec26d792c3b95f89ca8623872f3992b7a724826",
+            query::<String>(&db, &format!("select blob_to_hex(blob_xor({}, {}))", a, b))?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                &format!(
+                    "select blob_xor(blob_xor({a}, {b}), {b}) = {a}",
+                    a = a,
+                    b = b
+                )
+            )?
+        );
+        for (sql, expected) in &[
+            ("select hex(blob_and(x'f0f0', x'ff00'))", "F000"),
+            ("select hex(blob_or(x'f0f0', x'0f00'))", "FFF0"),
+            ("select hex(blob_xor(x'ffff', x'0f', true))", "FFF0"),
+            ("select hex(blob_and(x'01', x'ffff', 1))", "0001"),
+            ("select hex(blob_not(x'f00f'))", "0FF0"),
+            ("select hex(blob_xor(x'', x''))", ""),
+            ("select hex(blob_not(x''))", ""),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        assert!(query_err(&db, "select blob_xor(x'ffff', x'0f')")
+            .contains("blob lengths differ (2 and 1 bytes)"));
+        assert!(
+            query_err(&db, "select blob_or(x'ffff', x'0f', false)").contains("blob lengths differ")
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select blob_and(x'ff', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blob_concat_and_reverse_work() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "CAFEBABE",
+            query::<String>(&db, "select hex(blob_concat(x'ca', x'', x'febabe'))")?
+        );
+        assert_eq!("", query::<String>(&db, "select hex(blob_concat())")?);
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select blob_concat(x'ca', null)")?
+        );
+        assert!(query_err(&db, "select blob_concat(x'ca', 'fe')")
+            .contains("expected BLOB argument, got TEXT"));
+        assert_eq!(
+            "BEBAFECA",
+            query::<String>(&db, "select hex(blob_reverse(x'cafebabe'))")?
+        );
+        assert_eq!("", query::<String>(&db, "select hex(blob_reverse(x''))")?);
+        Ok(())
+    }
+
+    #[test]
+    fn coin_id_can_be_computed_in_sql() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // A streamable Coin (parent coin id, puzzle hash, u64 amount) worth
+        // 1 XCH. Coin ids hash the minimal CLVM encoding of the amount, which
+        // for 10^12 mojos is its last 6 bytes (including a leading zero).
+        let coin_id = query::<String>(
+            &db,
+            "with t(coin) as (select blob_concat(
+                 x'abababababababababababababababababababababababababababababababab',
+                 x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669',
+                 x'000000e8d4a51000'
+             ))
+             select blob_to_hex(sha256sum(blob_concat(
+                 blob_slice(coin, 0, 32), blob_slice(coin, 32, 32), blob_slice(coin, -6)
+             ))) from t",
+        )?;
+        assert_eq!(
+            "0692307ba1e14997bd556f291f5412f2641dee88eef21e59f765060ca57f1eca",
+            coin_id
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_blob_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (value, expected) in &[
+            ("0", "0000000000000000"),
+            ("1000000000000", "000000e8d4a51000"),
+            ("9223372036854775807", "7fffffffffffffff"),
+            ("'9223372036854775808'", "8000000000000000"),
+            ("'18446744073709551615'", "ffffffffffffffff"),
+        ] {
+            let sql = format!("select blob_to_hex(chia_amount_blob({}))", value);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+            let sql = format!(
+                "select chia_amount_text(chia_amount_blob({})) = trim({}, '''')",
+                value, value
+            );
+            assert_eq!(1, query::<i64>(&db, &sql)?, "{}", sql);
+        }
+        for n in &[0, 1, 1_000_000_000_000, i64::MAX] {
+            assert_eq!(
+                *n,
+                query::<i64>(
+                    &db,
+                    &format!("select chia_amount_int(chia_amount_blob({}))", n)
+                )?
+            );
+        }
+        assert_eq!(
+            "18446744073709551615",
+            query::<String>(
+                &db,
+                "select chia_amount_uint(chia_amount_blob('18446744073709551615'))"
+            )?
+        );
+        for (sql, expected) in &[
+            (
+                "select chia_amount_blob(-1)",
+                "value must not be negative, got -1",
+            ),
+            (
+                "select chia_amount_blob('18446744073709551616')",
+                "invalid unsigned integer '18446744073709551616': \
+                 number too large to fit in target type",
+            ),
+            (
+                "select chia_amount_blob(1.5)",
+                "expected INTEGER argument, got REAL",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select chia_amount_blob(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mojo_to_xch_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (mojos, expected) in &[
+            ("0", "0"),
+            ("1", "0.000000000001"),
+            ("1000000000000", "1"),
+            ("1500000000000", "1.5"),
+            ("1000000000001", "1.000000000001"),
+            ("x'000000e8d4a51000'", "1"),
+            ("x'01'", "0.000000000001"),
+            ("'18446744073709551615'", "18446744.073709551615"),
+            ("x'ffffffffffffffff'", "18446744.073709551615"),
+        ] {
+            let sql = format!("select mojo_to_xch({})", mojos);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            (
+                "select mojo_to_xch(-1)",
+                "value must not be negative, got -1",
+            ),
+            (
+                "select mojo_to_xch(x'000000000000000001')",
+                "amount blob must be at most 8 bytes, got 9 bytes",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select mojo_to_xch(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn xch_to_mojo_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (xch, expected) in &[
+            ("'0.000000000001'", Value::Integer(1)),
+            ("'1'", Value::Integer(1_000_000_000_000)),
+            ("1", Value::Integer(1_000_000_000_000)),
+            ("'1.5'", Value::Integer(1_500_000_000_000)),
+            ("1.5", Value::Integer(1_500_000_000_000)),
+            ("'.5'", Value::Integer(500_000_000_000)),
+            ("'2.'", Value::Integer(2_000_000_000_000)),
+            // Not 99999999999.99999 as 0.1 * 10^12 in floating point.
+            ("0.1", Value::Integer(100_000_000_000)),
+            ("'9223372.036854775807'", Value::Integer(i64::MAX)),
+            (
+                "'18446744.073709551615'",
+                Value::Text("18446744073709551615".to_string()),
+            ),
+            ("'0'", Value::Integer(0)),
+        ] {
+            let sql = format!("select xch_to_mojo({})", xch);
+            assert_eq!(*expected, query::<Value>(&db, &sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            (
+                "select xch_to_mojo('0.0000000000001')",
+                "amount '0.0000000000001' has more than 12 decimal places",
+            ),
+            (
+                "select xch_to_mojo('18446744.073709551616')",
+                "amount '18446744.073709551616' exceeds the largest amount with 12 \
+                 decimal places, 18446744.073709551615",
+            ),
+            (
+                "select xch_to_mojo(18446745)",
+                "amount '18446745' exceeds the largest amount with 12 decimal \
+                 places, 18446744.073709551615",
+            ),
+            (
+                "select xch_to_mojo('-1')",
+                "amount must not be negative, got '-1'",
+            ),
+            ("select xch_to_mojo('1e3')", "invalid amount '1e3'"),
+            ("select xch_to_mojo('.')", "invalid amount '.'"),
+            ("select xch_to_mojo('')", "invalid amount ''"),
+            (
+                "select xch_to_mojo(x'01')",
+                "expected TEXT, REAL or INTEGER argument, got BLOB",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            "18446744.073709551615",
+            query::<String>(
+                &db,
+                "select mojo_to_xch(xch_to_mojo('18446744.073709551615'))"
+            )?
+        );
+        assert_eq!(None, query::<Option<i64>>(&db, "select xch_to_mojo(null)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn format_mojo_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Pinned, so dashboards don't change between releases.
+        for (args, expected) in &[
+            ("0", "0 mojo"),
+            ("1", "1 mojo"),
+            ("250", "250 mojo"),
+            ("999", "999 mojo"),
+            ("999999", "999999 mojo"),
+            ("1000000", "0.000001 XCH"),
+            ("1000000000000", "1 XCH"),
+            ("1750000000000", "1.75 XCH"),
+            ("x'000000e8d4a51000'", "1 XCH"),
+            ("'18446744073709551615'", "18446744.073709551615 XCH"),
+            ("x'ffffffffffffffff'", "18446744.073709551615 XCH"),
+            ("1, 'xch'", "0.000000000001 XCH"),
+            ("0, 'xch'", "0 XCH"),
+            ("1000000000000, 'mojo'", "1000000000000 mojo"),
+            ("1000000000000, 'MOJO'", "1000000000000 mojo"),
+            (
+                "'18446744073709551615', 'mojo'",
+                "18446744073709551615 mojo",
+            ),
+            ("1754999999999, 'auto', 2", "1.75 XCH"),
+            ("1755000000000, 'auto', 2", "1.76 XCH"),
+            ("1999999999999, 'xch', 3", "2 XCH"),
+            ("1, 'xch', 6", "0 XCH"),
+            ("x'ffffffffffffffff', 'xch', 2", "18446744.07 XCH"),
+            ("x'ffffffffffffffff', 'xch', 0", "18446744 XCH"),
+            ("999, 'auto', 0", "999 mojo"),
+        ] {
+            let sql = format!("select format_mojo({})", args);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            (
+                "select format_mojo(1, 'chia')",
+                "unknown unit 'chia' (expected 'auto', 'mojo' or 'xch')",
+            ),
+            (
+                "select format_mojo(1, 'xch', 13)",
+                "fractional digits must be between 0 and 12, got 13",
+            ),
+            (
+                "select format_mojo(-1)",
+                "value must not be negative, got -1",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            None,
+            query::<Option<String>>(&db, "select format_mojo(1, null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cat_amounts_use_3_decimals() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select mojo_to_cat(1)", "0.001"),
+            ("select mojo_to_cat(1000)", "1"),
+            ("select mojo_to_cat(1234567)", "1234.567"),
+            ("select mojo_to_cat(x'03e8')", "1"),
+            ("select mojo_to_amount(1234567, 3)", "1234.567"),
+            ("select mojo_to_amount(1234567, 0)", "1234567"),
+            ("select mojo_to_amount(1, 18)", "0.000000000000000001"),
+            (
+                "select mojo_to_amount(x'ffffffffffffffff', 18)",
+                "18.446744073709551615",
+            ),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            ("select cat_to_mojo('0.001')", 1),
+            ("select cat_to_mojo('1234.567')", 1234567),
+            ("select cat_to_mojo(1.5)", 1500),
+            ("select cat_to_mojo(2)", 2000),
+            ("select amount_to_mojo('1234.567', 3)", 1234567),
+            ("select amount_to_mojo('1234567', 0)", 1234567),
+            ("select amount_to_mojo(1234567, 0)", 1234567),
+            ("select amount_to_mojo('7.', 0)", 7),
+            ("select amount_to_mojo('0.000000000000000001', 18)", 1),
+            (
+                "select amount_to_mojo(mojo_to_amount(987654321, 6), 6)",
+                987654321,
+            ),
+        ] {
+            assert_eq!(*expected, query::<i64>(&db, sql)?, "{}", sql);
+        }
+        for (sql, expected) in &[
+            (
+                "select cat_to_mojo('0.0001')",
+                "amount '0.0001' has more than 3 decimal places",
+            ),
+            (
+                "select amount_to_mojo('1.5', 0)",
+                "amount '1.5' has more than 0 decimal places",
+            ),
+            (
+                "select amount_to_mojo('19', 18)",
+                "amount '19' exceeds the largest amount with 18 decimal places, \
+                 18.446744073709551615",
+            ),
+            (
+                "select cat_to_mojo('18446744073709551.616')",
+                "amount '18446744073709551.616' exceeds the largest amount with 3 \
+                 decimal places, 18446744073709551.615",
+            ),
+            (
+                "select mojo_to_amount(1, 19)",
+                "decimals must be between 0 and 18, got 19",
+            ),
+            (
+                "select amount_to_mojo('1', -1)",
+                "value must not be negative, got -1",
+            ),
+        ] {
+            assert_eq!(*expected, query_err(&db, sql), "{}", sql);
+        }
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select mojo_to_amount(1000000000001, 12) = mojo_to_xch(1000000000001)"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select amount_to_mojo('1', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn coin_can_be_built_with_chia_amount_blob() -> anyhow::Result<()> {
+        let db = open_db()?;
+        db.execute_batch(
+            "create temp table coins as select
+               x'abababababababababababababababababababababababababababababababab' as parent,
+               x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669' as puzzle_hash,
+               1000000000000 as amount;",
+        )?;
+        // The streamable Coin from `coin_id_can_be_computed_in_sql`.
+        assert_eq!(
+            "abababababababababababababababababababababababababababababababab\
+             f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669\
+             000000e8d4a51000",
+            query::<String>(
+                &db,
+                "select blob_to_hex(blob_concat(parent, puzzle_hash, chia_amount_blob(amount))) \
+                 from coins"
+            )?
+        );
+        // Coin ids hash the minimal CLVM encoding of the amount rather than
+        // the 8-byte blob.
+        assert_eq!(
+            "0692307ba1e14997bd556f291f5412f2641dee88eef21e59f765060ca57f1eca",
+            query::<String>(
+                &db,
+                "select blob_to_hex(sha256sum(parent, puzzle_hash, clvm_int_encode(amount))) \
+                 from coins"
+            )?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select sha256sum(parent, puzzle_hash, clvm_int_encode(amount)) = \
+                        sha256sum(parent, puzzle_hash, chia_amount_blob(amount)) \
+                 from coins"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn block_rewards_follow_the_schedule() -> anyhow::Result<()> {
+        let db = open_db()?;
+        const XCH: i64 = 1_000_000_000_000;
+        for &(height, total) in &[
+            (1, 2 * XCH),
+            // Rewards don't change when transactions started.
+            (225698, 2 * XCH),
+            (5045759, 2 * XCH),
+            (5045760, XCH),
+            (10091519, XCH),
+            (10091520, XCH / 2),
+            (15137279, XCH / 2),
+            (15137280, XCH / 4),
+            (20183039, XCH / 4),
+            (20183040, XCH / 8),
+            // No further halvings.
+            (25228800, XCH / 8),
+            (i64::from(u32::MAX), XCH / 8),
+            (i64::MAX, XCH / 8),
+        ] {
+            let rewards = db.query_row(
+                "select chia_block_reward(?1), chia_pool_reward(?1), chia_farmer_reward(?1)",
+                [height],
+                |r| {
+                    Ok((
+                        r.get::<usize, i64>(0)?,
+                        r.get::<usize, i64>(1)?,
+                        r.get::<usize, i64>(2)?,
+                    ))
+                },
+            )?;
+            assert_eq!(
+                (total, total / 8 * 7, total / 8),
+                rewards,
+                "height {}",
+                height
+            );
+        }
+        // The genesis block pays out the prefarm, more than fits an integer.
+        assert_eq!(
+            (
+                "21000000000000000000".to_string(),
+                "18375000000000000000".to_string(),
+                2_625_000_000_000_000_000,
+            ),
+            db.query_row(
+                "select chia_block_reward(0), chia_pool_reward(0), chia_farmer_reward(0)",
+                [],
+                |r| Ok((
+                    r.get::<usize, String>(0)?,
+                    r.get::<usize, String>(1)?,
+                    r.get::<usize, i64>(2)?
+                )),
+            )?
+        );
+        assert_eq!(
+            "invalid block height: value must not be negative, got -1",
+            query_err(&db, "select chia_block_reward(-1)")
+        );
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select chia_farmer_reward(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_reward_schedule_lists_eras() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            (6, 0, i64::MAX),
+            db.query_row(
+                "select count(*), min(start_height), max(end_height) from chia_reward_schedule",
+                [],
+                |r| Ok((
+                    r.get::<usize, i64>(0)?,
+                    r.get::<usize, i64>(1)?,
+                    r.get::<usize, i64>(2)?
+                )),
+            )?
+        );
+        let mut stmt = db.prepare(
+            "select start_height, end_height, cast(reward_mojos as text), reward_xch_text
+             from chia_reward_schedule order by start_height",
+        )?;
+        let eras = stmt
+            .query_map([], |r| {
+                Ok((
+                    r.get::<usize, i64>(0)?,
+                    r.get::<usize, i64>(1)?,
+                    r.get::<usize, String>(2)?,
+                    r.get::<usize, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let era =
+            |start, end, mojos: &str, xch: &str| (start, end, mojos.to_string(), xch.to_string());
+        assert_eq!(
+            vec![
+                era(0, 0, "21000000000000000000", "21000000"),
+                era(1, 5045759, "2000000000000", "2"),
+                era(5045760, 10091519, "1000000000000", "1"),
+                era(10091520, 15137279, "500000000000", "0.5"),
+                era(15137280, 20183039, "250000000000", "0.25"),
+                era(20183040, i64::MAX, "125000000000", "0.125"),
+            ],
+            eras
+        );
+        // Joining heights against eras agrees with chia_block_reward.
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "with heights(height) as (values (0), (1), (5045759), (5045760), (10091520),
+                     (20183039), (20183040), (55503359))
+                 select count(*) from heights
+                 left join chia_reward_schedule()
+                     on height between start_height and end_height
+                 where reward_mojos is not chia_block_reward(height)"
+            )?
+        );
+        // Heights far in the future fall into the last, open-ended era.
+        assert_eq!(
+            (1, 1),
+            db.query_row(
+                "select count(*), sum(reward_mojos = chia_block_reward(height))
+                 from (select 60000000 as height)
+                 join chia_reward_schedule on height between start_height and end_height",
+                [],
+                |r| Ok((r.get::<usize, i64>(0)?, r.get::<usize, i64>(1)?)),
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_agg_sig_message_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let coin = "x'e47125968b3b71049fbc4802d1e40a71ea1359decfabacf70b34588037d4ff0c', \
+                    x'c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0da545364'";
+        for (args, expected) in &[
+            (
+                format!("50, x'7790575def7eb07764eca00a31a8a1d220971b5b3f52a077fb390060d564fc83', {}, 1000, 'mainnet'", coin),
+                "7790575def7eb07764eca00a31a8a1d220971b5b3f52a077fb390060d564fc83\
+                 31acbd5a923c532bfde931b1701e290a0e55f4deba926ecc631fd0b3c78fb399\
+                 ccd5bb71183532bff220ba46c268991a3ff07eb358e8255a65c30a2dce0e5fbb",
+            ),
+            (
+                format!("43, 'chia', {}, 1000, 'mainnet'", coin),
+                "63686961e47125968b3b71049fbc4802d1e40a71ea1359decfabacf70b34588037d4ff0c\
+                 baf5d69c647c91966170302d18521b0a85663433d161e72c826ed08677b53a74",
+            ),
+            (
+                format!("46, 'chia', {}, 1000, 'testnet11'", coin),
+                "63686961c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0da54536403e8\
+                 02c0ecb453e75bd77823dd0affd3f224d968012a8c6c6c423801cc30dd5eb347",
+            ),
+            (
+                format!("45, 'chia', {}, '18446744073709551615', 'mainnet'", coin),
+                "6368696100ffffffffffffffff\
+                 cda186a9cd030f7a130fae45005e81cae7a90e0fa205b75f6aebc0d598e0348e",
+            ),
+            (
+                format!("49, 'chia', {}, 1000, 'mainnet'", coin),
+                "63686961",
+            ),
+        ] {
+            assert_eq!(
+                *expected,
+                query::<String>(&db, &format!("select lower(hex(chia_agg_sig_message({})))", args))?,
+                "{}",
+                args
+            );
+        }
+        // An explicit genesis challenge works like the network's name.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                &format!(
+                    "select chia_agg_sig_message(47, 'chia', {0}, 1, 'testnet11') = \
+                            chia_agg_sig_message(47, 'chia', {0}, 1, \
+                              x'37a90eb5185a9c4439a91ddc98bbadce7b4feba060d50116a067de66bf236615')",
+                    coin
+                )
+            )?
+        );
+        for (args, expected) in &[
+            (
+                format!("51, 'chia', {}, 1, 'mainnet'", coin),
+                "opcode must be an AGG_SIG condition (43 to 50), got 51",
+            ),
+            (
+                format!("306, 'chia', {}, 1, 'mainnet'", coin),
+                "opcode must be an AGG_SIG condition (43 to 50), got 306",
+            ),
+            (
+                format!("50, 'chia', {}, -1, 'mainnet'", coin),
+                "value must not be negative, got -1",
+            ),
+            (
+                format!("50, 'chia', {}, 1, 'testnet10'", coin),
+                "unknown network 'testnet10' (expected 'mainnet', 'testnet11' or a genesis challenge)",
+            ),
+            (
+                format!("50, 'chia', {}, 1, x'cafe'", coin),
+                "genesis challenge must be 32 bytes long, got 2 bytes",
+            ),
+            (
+                "50, 'chia', x'cafe', zeroblob(32), 1, 'mainnet'".to_string(),
+                "parent coin ID must be 32 bytes long, got 2 bytes",
+            ),
+        ] {
+            let sql = format!("select chia_agg_sig_message({})", args);
+            assert_eq!(*expected, query_err(&db, &sql), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "clvm")]
+    #[test]
+    fn chia_spendbundle_verify_sig_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // A standard transaction spending a 1000-mojo coin of the synthetic
+        // key of the first observer address of "abandon ... art", recreating
+        // the coin with the delegated puzzle (q . ((51 puzzle_hash 1000))).
+        let sql = "select blob_concat(\
+              x'00000001',\
+              x'e47125968b3b71049fbc4802d1e40a71ea1359decfabacf70b34588037d4ff0c',\
+              x'c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0da545364',\
+              x'00000000000003e8',\
+              x'ff02ffff01ff02ffff01ff02ffff03ff0bffff01ff02ffff03ffff09ff05ffff\
+                1dff0bffff1effff0bff0bffff02ff06ffff04ff02ffff04ff17ff8080808080\
+                808080ffff01ff02ff17ff2f80ffff01ff088080ff0180ffff01ff04ffff04ff\
+                04ffff04ff05ffff04ffff02ff06ffff04ff02ffff04ff17ff80808080ff8080\
+                8080ffff02ff17ff2f808080ff0180ffff04ffff01ff32ff02ffff03ffff07ff\
+                0580ffff01ff0bffff0102ffff02ff06ffff04ff02ffff04ff09ff80808080ff\
+                ff02ff06ffff04ff02ffff04ff0dff8080808080ffff01ff0bffff0101ff0580\
+                80ff0180ff018080ffff04ffff01b0a4c8f441b4118b71df6b8f0d63062df10b\
+                3c03bfd9aac5d59eae1c018f5fc1c91fd8c69da347eb6c4433e896a3a0601aff\
+                018080',\
+              x'ff80ffff01ffff33ffa0c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0\
+                da545364ff8203e88080ff8080',\
+              bls_sign(\
+                x'6103e1614b6724dc3975ddc1cd16c3350fe13edd72c686a539a89da488b17a1d',\
+                chia_agg_sig_message(\
+                  50,\
+                  x'7790575def7eb07764eca00a31a8a1d220971b5b3f52a077fb390060d564fc83',\
+                  x'e47125968b3b71049fbc4802d1e40a71ea1359decfabacf70b34588037d4ff0c',\
+                  x'c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0da545364',\
+                  1000,\
+                  'mainnet')))";
+        let bundle = query::<Vec<u8>>(&db, sql)?;
+        let verify = |bundle: &[u8], network: &str| -> rusqlite::Result<i64> {
+            db.query_row(
+                &format!("select chia_spendbundle_verify_sig(?, '{}')", network),
+                rusqlite::params![bundle],
+                |r| r.get(0),
+            )
+        };
+        assert_eq!(1, verify(&bundle, "mainnet")?);
+        assert_eq!(0, verify(&bundle, "testnet11")?);
+        // A flipped bit in the signature, in the amount the solution creates
+        // a coin with and in the puzzle reveal.
+        let signature = bundle.len() - 96;
+        for offset in &[signature, signature + 95] {
+            let mut tampered = bundle.clone();
+            tampered[*offset] ^= 1;
+            assert_eq!(0, verify(&tampered, "mainnet")?, "{}", offset);
+        }
+        let amount = signature - 7;
+        assert_eq!(&[0x03, 0xe8], &bundle[amount..amount + 2]);
+        let mut tampered = bundle.clone();
+        tampered[amount + 1] ^= 1;
+        assert_eq!(0, verify(&tampered, "mainnet")?);
+        let mut tampered = bundle.clone();
+        tampered[80] ^= 1;
+        assert_eq!(
+            "puzzle reveal of coin spend 1 doesn't match the coin's puzzle hash",
+            verify(&tampered, "mainnet").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "unexpected end of spend bundle after 50 bytes",
+            verify(&bundle[..50], "mainnet").unwrap_err().to_string()
+        );
+        // Puzzles outputting (50 <point at infinity> 0xcafe) and an AGG_SIG_ME
+        // condition missing its message, with the empty aggregate signature.
+        for puzzle in &[
+            "ff01ffff32ffb0c0000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000ff82cafe8080",
+            "ff01ffff32ffb0c0000000000000000000000000000000000000000000000000\
+             00000000000000000000000000000000000000000000008080",
+        ] {
+            let bundle = query::<Vec<u8>>(
+                &db,
+                &format!(
+                    "select blob_concat(x'00000001', zeroblob(32), sha256tree(x'{0}'), \
+                     zeroblob(8), x'{0}', x'80', x'c0', zeroblob(95))",
+                    puzzle
+                ),
+            )?;
+            assert_eq!(0, verify(&bundle, "mainnet")?, "{}", puzzle);
+        }
+        // A puzzle calling an unknown operator, with the empty aggregate
+        // signature.
+        let unknown_op = query::<Vec<u8>>(
+            &db,
+            "select blob_concat(x'00000001', zeroblob(32), sha256tree(x'ff7e80'), \
+             zeroblob(8), x'ff7e80', x'80', x'c0', zeroblob(95))",
+        )?;
+        let err = verify(&unknown_op, "mainnet").unwrap_err().to_string();
+        assert!(
+            err.starts_with("puzzle of coin spend 1 failed: "),
+            "{}",
+            err
+        );
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select chia_spendbundle_verify_sig(blob_concat(x'00000000', zeroblob(96)), 'mainnet')"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn announcement_ids_work() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let coin_id = "x'0692307ba1e14997bd556f291f5412f2641dee88eef21e59f765060ca57f1eca'";
+        let puzzle_hash = "x'f4f6ca53d56211869b1705ce29726bad7a67d30ebe002a65450b13adbb05a669'";
+        for (sql, expected) in &[
+            (
+                format!("chia_coin_announcement_id({}, x'cafe')", coin_id),
+                "ec5e7be3cdfedd7e32046cac5ead58c9aed4837f12a4d045a1b3607ad5aae404",
+            ),
+            (
+                format!("chia_puzzle_announcement_id({}, x'cafe')", puzzle_hash),
+                "2811268edabbfc8048887338b79df526dd0f26a31d5ac1f7b00e3df8a91052ae",
+            ),
+            (
+                format!("chia_puzzle_announcement_id({}, x'')", puzzle_hash),
+                "a66f8e299c6aa07f12040de904e68fb782416e09bccc1929e479fa831e5f2261",
+            ),
+            (
+                format!(
+                    "chia_coin_announcement_id(blob_to_hex({}), 'cafe')",
+                    coin_id
+                ),
+                "ec5e7be3cdfedd7e32046cac5ead58c9aed4837f12a4d045a1b3607ad5aae404",
+            ),
+        ] {
+            let sql = format!("select blob_to_hex({})", sql);
+            assert_eq!(*expected, query::<String>(&db, &sql)?, "{}", sql);
+        }
+        assert_eq!(
+            query::<Vec<u8>>(&db, &format!("select sha256sum({}, x'cafe')", coin_id))?,
+            query::<Vec<u8>>(
+                &db,
+                &format!("select chia_coin_announcement_id({}, x'cafe')", coin_id)
+            )?
+        );
+        assert_eq!(
+            "coin ID must be 32 bytes long, got 2 bytes",
+            query_err(&db, "select chia_coin_announcement_id(x'cafe', x'')")
+        );
+        assert_eq!(
+            "puzzle hash must be 32 bytes long, got 33 bytes",
+            query_err(
+                &db,
+                &format!(
+                    "select chia_puzzle_announcement_id(blob_concat({}, x'00'), x'')",
+                    puzzle_hash
+                )
+            )
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(
+                &db,
+                &format!("select chia_coin_announcement_id({}, null)", coin_id)
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn be_and_le_uint_work() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select be_uint(x'')", Value::Integer(0)),
+            ("select le_uint(x'')", Value::Integer(0)),
+            ("select be_uint(x'2a')", Value::Integer(42)),
+            ("select le_uint(x'2a')", Value::Integer(42)),
+            ("select be_uint(x'0102')", Value::Integer(0x0102)),
+            ("select le_uint(x'0102')", Value::Integer(0x0201)),
+            ("select be_uint(x'003d0900')", Value::Integer(4_000_000)),
+            ("select le_uint(x'00093d00')", Value::Integer(4_000_000)),
+            (
+                "select be_uint(x'0102030405060708')",
+                Value::Integer(0x0102_0304_0506_0708),
+            ),
+            (
+                "select le_uint(x'0102030405060708')",
+                Value::Integer(0x0807_0605_0403_0201),
+            ),
+            (
+                "select be_uint(x'ffffffffffffffff')",
+                Value::Text("18446744073709551615".to_string()),
+            ),
+            (
+                "select be_uint(x'00000000000000000000000000000400')",
+                Value::Integer(1024),
+            ),
+            (
+                "select le_uint(x'00040000000000000000000000000000')",
+                Value::Integer(1024),
+            ),
+            ("select be_uint(null)", Value::Null),
+        ] {
+            assert_eq!(*expected, query::<Value>(&db, sql)?, "{}", sql);
+        }
+        assert!(query_err(&db, "select be_uint(x'010000000000000000')")
+            .contains("9-byte integer does not fit into a signed 64-bit integer"));
+        assert!(query_err(&db, "select be_uint(x'008000000000000000')")
+            .contains("does not fit into a signed 64-bit integer"));
+        Ok(())
+    }
+
+    #[test]
+    fn uint_to_be_blob_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select hex(uint_to_be_blob(0, 0))", ""),
+            ("select hex(uint_to_be_blob(0, 4))", "00000000"),
+            ("select hex(uint_to_be_blob(4000000, 4))", "003D0900"),
+            (
+                "select hex(uint_to_be_blob(1, 16))",
+                "00000000000000000000000000000001",
+            ),
+            (
+                "select hex(uint_to_be_blob('18446744073709551615', 8))",
+                "FFFFFFFFFFFFFFFF",
+            ),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        for &value in &[
+            0,
+            1,
+            127,
+            128,
+            255,
+            256,
+            65_535,
+            4_000_000,
+            4_294_967_295,
+            i64::MAX,
+        ] {
+            for &width in &[1u32, 2, 4, 8] {
+                if width < 8 && value >= 1 << (8 * width) {
+                    continue;
+                }
+                let (len, roundtrip) = db.query_row(
+                    "select length(uint_to_be_blob(?1, ?2)), be_uint(uint_to_be_blob(?1, ?2))",
+                    rusqlite::params![value, width],
+                    |r| Ok((r.get::<usize, u32>(0)?, r.get::<usize, i64>(1)?)),
+                )?;
+                assert_eq!((width, value), (len, roundtrip));
+            }
+        }
+        assert!(query_err(&db, "select uint_to_be_blob(256, 1)")
+            .contains("value 256 does not fit into 1 bytes"));
+        assert!(
+            query_err(&db, "select uint_to_be_blob(-1, 8)").contains("value must not be negative")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_from_minimal_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select chia_amount_from_minimal(x'')", Value::Integer(0)),
+            ("select chia_amount_from_minimal(x'01')", Value::Integer(1)),
+            (
+                "select chia_amount_from_minimal(x'00ff')",
+                Value::Integer(255),
+            ),
+            (
+                "select chia_amount_from_minimal(x'00e8d4a51000')",
+                Value::Integer(1_000_000_000_000),
+            ),
+            (
+                "select chia_amount_from_minimal(x'00ffffffffffffffff')",
+                Value::Text("18446744073709551615".to_string()),
+            ),
+            ("select chia_amount_from_minimal(null)", Value::Null),
+        ] {
+            assert_eq!(*expected, query::<Value>(&db, sql)?, "{}", sql);
+        }
+        assert!(query_err(&db, "select chia_amount_from_minimal(x'ff')")
+            .contains("amount must not be negative"));
+        assert!(query_err(
+            &db,
+            "select chia_amount_from_minimal(x'010000000000000000')"
+        )
+        .contains("amount does not fit into 64 bits (9 significant bytes)"));
+        Ok(())
+    }
+
+    #[test]
+    fn chia_amount_uint_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            Value::Integer(5509699999997),
+            query::<Value>(&db, "select chia_amount_uint(x'00000502D3B618FD')")?
+        );
+        assert_eq!(
+            Value::Integer(i64::MAX),
+            query::<Value>(&db, "select chia_amount_uint(x'7FFFFFFFFFFFFFFF')")?
+        );
+        assert_eq!(
+            Value::Text("9223372036854775808".to_string()),
+            query::<Value>(&db, "select chia_amount_uint(x'8000000000000000')")?
+        );
+        assert_eq!(
+            Value::Text("18446744073709551615".to_string()),
+            query::<Value>(&db, "select chia_amount_uint(x'FFFFFFFFFFFFFFFF')")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256sum_works() -> anyhow::Result<()> {
         let db = open_db()?;
         assert_eq!(
             "03346F0E7990DE2423A3BCA5335BF92CDC0BD14BEF2206B87C63F18A1E996C52".to_string(),
@@ -192,6 +7473,709 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn clvm_tree_hash_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (program, expected) in &[
+            // (q . 1)
+            (
+                "ff0101",
+                "69ae360134b1fae04326e5546f25dc794a19192a1f22a44a46d038e7f0d1ecbb",
+            ),
+            // ()
+            (
+                "80",
+                "4bf5122f344554c53bde2ebb8cd2b7e3d1600ad631c385a5d7cce23c7785459a",
+            ),
+            // 1
+            (
+                "01",
+                "9dcf97a184f32623d11a73124ceb99a5709b083721e878a16d78f596718ba7b2",
+            ),
+            // (a (q . 1) ())
+            (
+                "ff02ffff0101ff8080",
+                "3e4741103fe84bf413b5418e15522471da9622ebde2a6f556a0a599b82ec4945",
+            ),
+            // (x), the default hidden puzzle of the standard transaction
+            (
+                "ff0980",
+                "711d6c4e32c92e53179b199484cf8c897542bc57f2b22582799f9d657eec4699",
+            ),
+            // A 32-byte atom behind a one-byte size prefix.
+            (
+                "a0cafecafecafecafecafecafecafecafecafecafecafecafecafecafecafecafe",
+                "8a57af1b5bcfb2bd07c5f414649c9a08ef812969128992a51e00f8eafc3b7088",
+            ),
+            // A 64-byte atom behind a two-byte size prefix.
+            (
+                "c040\
+                 0000000000000000000000000000000000000000000000000000000000000000\
+                 0000000000000000000000000000000000000000000000000000000000000000",
+                "ae0798d0ecaed2b778eddebf18f071a561c53658c05e76cedecc27cafbdbc577",
+            ),
+        ] {
+            assert_eq!(
+                *expected,
+                query::<String>(
+                    &db,
+                    &format!("select blob_to_hex(clvm_tree_hash(x'{}'))", program)
+                )?,
+                "{}",
+                program
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn clvm_tree_hash_rejects_malformed_programs() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (program, message) in &[
+            ("", "unexpected end of CLVM program after 0 bytes"),
+            ("ff01", "unexpected end of CLVM program after 2 bytes"),
+            ("83cafe", "unexpected end of CLVM program after 3 bytes"),
+            (
+                "0101",
+                "trailing bytes after CLVM program (1 of 2 bytes used)",
+            ),
+            (
+                "ff01fe01",
+                "CLVM back references are not supported (at offset 2)",
+            ),
+        ] {
+            let err = query_err(&db, &format!("select clvm_tree_hash(x'{}')", program));
+            assert!(err.contains(message), "{}: {}", program, err);
+        }
+        // Deep nesting must not overflow the stack.
+        let depth = 100_000;
+        let deep = format!("{}80{}", "ff".repeat(depth), "80".repeat(depth));
+        assert_eq!(
+            32,
+            query::<i64>(&db, &format!("select length(clvm_tree_hash(x'{}'))", deep))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha256tree_computes_puzzle_hashes() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The standard transaction puzzle curried with the synthetic key used
+        // in chia_spendbundle_verify_sig_works, and the hash of its coin.
+        db.execute_batch(
+            "create table coin_spend (puzzle_reveal blob, puzzle_hash blob);
+             insert into coin_spend values (\
+                 x'ff02ffff01ff02ffff01ff02ffff03ff0bffff01ff02ffff03ffff09ff05ffff\
+                   1dff0bffff1effff0bff0bffff02ff06ffff04ff02ffff04ff17ff8080808080\
+                   808080ffff01ff02ff17ff2f80ffff01ff088080ff0180ffff01ff04ffff04ff\
+                   04ffff04ff05ffff04ffff02ff06ffff04ff02ffff04ff17ff80808080ff8080\
+                   8080ffff02ff17ff2f808080ff0180ffff04ffff01ff32ff02ffff03ffff07ff\
+                   0580ffff01ff0bffff0102ffff02ff06ffff04ff02ffff04ff09ff80808080ff\
+                   ff02ff06ffff04ff02ffff04ff0dff8080808080ffff01ff0bffff0101ff0580\
+                   80ff0180ff018080ffff04ffff01b0a4c8f441b4118b71df6b8f0d63062df10b\
+                   3c03bfd9aac5d59eae1c018f5fc1c91fd8c69da347eb6c4433e896a3a0601aff\
+                   018080',\
+                 x'c05d9ece06d5b53e34745d1f12bbfa882c3aad1acabc036c983839d0da545364');",
+        )?;
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select count(*) from coin_spend where sha256tree(puzzle_reveal) = puzzle_hash"
+            )?
+        );
+        // A list of 40000 32-byte atoms, larger than a full block generator.
+        let mut program = Vec::new();
+        for i in 0..40_000 {
+            program.extend_from_slice(&[0xff, 0xa0]);
+            program.extend_from_slice(&[(i % 256) as u8; 32]);
+        }
+        program.push(0x80);
+        assert_eq!(
+            "d6cc586aea31084fa91e2cba8e2d792516e3287c6570ae3381cc0f1918ec3309",
+            db.query_row(
+                "select blob_to_hex(sha256tree(?))",
+                rusqlite::params![program],
+                |r| r.get::<usize, String>(0),
+            )?
+        );
+        program.pop();
+        let err = db
+            .query_row("select sha256tree(?)", rusqlite::params![program], |r| {
+                r.get::<usize, Vec<u8>>(0)
+            })
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("unexpected end of CLVM program after 1360000 bytes"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clvm_int_roundtrips() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (n, atom) in &[
+            (0, ""),
+            (1, "01"),
+            (127, "7f"),
+            (128, "0080"),
+            (255, "00ff"),
+            (-1, "ff"),
+            (-128, "80"),
+            (-129, "ff7f"),
+            (1_000_000_000_000, "00e8d4a51000"),
+            (i64::MAX, "7fffffffffffffff"),
+            (i64::MIN, "8000000000000000"),
+        ] {
+            assert_eq!(
+                *atom,
+                db.query_row(
+                    "select blob_to_hex(clvm_int_encode(?))",
+                    rusqlite::params![n],
+                    |r| r.get::<usize, String>(0)
+                )?,
+                "{}",
+                n
+            );
+            assert_eq!(
+                *n,
+                query::<i64>(&db, &format!("select clvm_int_decode(x'{}')", atom))?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn clvm_int_encode_is_canonical() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (atom, canonical) in &[
+            ("0000007f", "7f"),
+            ("000080", "0080"),
+            ("ffff80", "80"),
+            ("ffffffff", "ff"),
+            ("0000", ""),
+            ("00000000000000000000000001", "01"),
+            ("ffffffffffffffffffffffff80", "80"),
+        ] {
+            assert_eq!(
+                *canonical,
+                query::<String>(
+                    &db,
+                    &format!(
+                        "select blob_to_hex(clvm_int_encode(clvm_int_decode(x'{}')))",
+                        atom
+                    )
+                )?,
+                "{}",
+                atom
+            );
+        }
+        for atom in &[
+            "010000000000000000",
+            "00ffffffffffffffff",
+            "ff7fffffffffffffff",
+        ] {
+            assert!(
+                query_err(&db, &format!("select clvm_int_decode(x'{}')", atom))
+                    .contains("does not fit into a signed 64-bit integer"),
+                "{}",
+                atom
+            );
+        }
+        assert!(query_err(&db, "select clvm_int_encode('1')")
+            .contains("expected INTEGER argument, got TEXT"));
+        Ok(())
+    }
+
+    #[test]
+    fn double_sha256_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "9a4f7fda912494551dc4ea7e0af0532da150fdaf5456b9574bc8b1e202712ff8",
+            query::<String>(&db, "select blob_to_hex(double_sha256(x'cafe'))")?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select double_sha256(x'cafe') = sha256sum(sha256sum(x'cafe'))"
+            )?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "blake2")]
+    #[test]
+    fn blake2b_256_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            // RFC 7693, appendix A
+            (
+                "select blob_to_hex(blake2b_256('abc', 64))",
+                "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+                 7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+            ),
+            // Computed with Python's hashlib.blake2b.
+            (
+                "select blob_to_hex(blake2b_256('abc'))",
+                "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+            ),
+            (
+                "select blob_to_hex(blake2b_256(x''))",
+                "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8",
+            ),
+            (
+                "select blob_to_hex(blake2b_256(x'cafe', 20))",
+                "99f5eb9036050a534fdc2244a728bb8e2154585e",
+            ),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        assert!(query_err(&db, "select blake2b_256(x'cafe', 65)")
+            .contains("digest length must be between 1 and 64, got 65"));
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select blake2b_256(null)")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            query::<String>(&db, "select blob_to_hex(blake3(x''))")?
+        );
+        assert_eq!(
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262\
+             e00f03e7b69af26b7faaf09fcd333050338ddfe085b8cc869ca98b206c08243a",
+            query::<String>(&db, "select blob_to_hex(blake3(x'', 64))")?
+        );
+        // Extended output starts with the default digest.
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select blob_slice(blake3(x'cafe', 64), 0, 32) = blake3(x'cafe', 32)
+                    and blake3(x'cafe', 32) = blake3(x'cafe')"
+            )?
+        );
+        assert!(query_err(&db, "select blake3(x'cafe', 0)")
+            .contains("output length must be between 1 and 65536, got 0"));
+        Ok(())
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_passes_official_test_vectors() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // Inputs of the official test vectors repeat the bytes 0 to 250.
+        let input = |len: usize| (0..len).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        for (len, expected) in &[
+            (
+                1,
+                "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213",
+            ),
+            (
+                1023,
+                "10108970eeda3eb932baac1428c7a2163b0e924c9a9e25b35bba72b28f70bd11",
+            ),
+            (
+                1024,
+                "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7",
+            ),
+            (
+                1025,
+                "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444",
+            ),
+            (
+                3072,
+                "b98cb0ff3623be03326b373de6b9095218513e64f1ee2edd2525c7ad1e5cffd2",
+            ),
+            (
+                31744,
+                "62b6960e1a44bcc1eb1a611a8d6235b6b4b78f32e7abc4fb4c6cdcce94895c47",
+            ),
+            (
+                102400,
+                "bc3e3d41a1146b069abffad3c0d44860cf664390afce4d9661f7902e7943e085",
+            ),
+            // Large enough to be hashed in parallel.
+            (
+                1 << 20,
+                "74cb441fd087764ca9c3694da742ebe30cbeb3060a17009ca81825c7a8d10343",
+            ),
+        ] {
+            let digest = db.query_row(
+                "select blob_to_hex(blake3(?))",
+                rusqlite::params![input(*len)],
+                |r| r.get::<usize, String>(0),
+            )?;
+            assert_eq!(*expected, digest, "{} bytes", len);
+        }
+        assert_eq!(
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            query::<String>(&db, "select blob_to_hex(blake3('abc'))")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "ripemd")]
+    #[test]
+    fn hash160_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        // The compressed secp256k1 generator point.
+        let pubkey = "x'0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798'";
+        assert_eq!(
+            "751e76e8199196d454941c45d1b3a323f1433bd6",
+            query::<String>(&db, &format!("select blob_to_hex(hash160({}))", pubkey))?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                &format!("select hash160({0}) = ripemd160(sha256sum({0}))", pubkey)
+            )?
+        );
+        // Text is hashed as its UTF-8 bytes, not decoded as hex.
+        assert_eq!(
+            "bb1be98c142444d7a56aa3981c3942a978e4dc33",
+            query::<String>(&db, "select blob_to_hex(hash160('abc'))")?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(&db, "select hash160('cafe') = ripemd160(sha256sum('cafe'))")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "sha3")]
+    #[test]
+    fn keccak256_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            query::<String>(&db, "select blob_to_hex(keccak256(x''))")?
+        );
+        // The ERC-20 Transfer event topic.
+        assert_eq!(
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+            query::<String>(
+                &db,
+                "select blob_to_hex(keccak256('Transfer(address,address,uint256)'))"
+            )?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "ripemd")]
+    #[test]
+    fn ripemd160_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "9c1185a5c5e9fc54612808977ee8f548b2258d31",
+            query::<String>(&db, "select blob_to_hex(ripemd160(x''))")?
+        );
+        assert_eq!(
+            "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc",
+            query::<String>(&db, "select blob_to_hex(ripemd160(cast('abc' as blob)))")?
+        );
+        assert_eq!(
+            "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc",
+            query::<String>(&db, "select blob_to_hex(ripemd160('abc'))")?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "sha3")]
+    #[test]
+    fn sha3_256_differs_from_keccak256() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+            query::<String>(&db, "select blob_to_hex(sha3_256(x''))")?
+        );
+        assert_eq!(
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            query::<String>(&db, "select blob_to_hex(sha3_256('abc'))")?
+        );
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select sha3_256(x'cafe') != keccak256(x'cafe')
+                    and keccak256('abc') = keccak256(x'616263')"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select sha3_256(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha512_and_sha256d_work() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            (
+                "select blob_to_hex(sha512(x''))",
+                "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                 47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            ),
+            (
+                "select blob_to_hex(sha512('abc'))",
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                 2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+            (
+                "select blob_to_hex(sha256d(x''))",
+                "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456",
+            ),
+            // The Bitcoin genesis block header; block hashes are displayed
+            // byte-reversed.
+            (
+                "select blob_to_hex(blob_reverse(sha256d(x'01000000\
+                     0000000000000000000000000000000000000000000000000000000000000000\
+                     3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a\
+                     29ab5f49ffff001d1dac2b7c')))",
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            ),
+        ] {
+            assert_eq!(*expected, query::<String>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            1,
+            query::<i64>(
+                &db,
+                "select sha512('abc') = sha512sum(x'616263')
+                    and sha256d('abc') = double_sha256(x'616263')"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn crc32_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select crc32(x'')", 0),
+            ("select crc32('123456789')", 0xcbf4_3926),
+            ("select crc32(x'313233343536373839')", 0xcbf4_3926),
+            ("select crc32(x'cafe')", 717_877_792),
+        ] {
+            assert_eq!(*expected, query::<i64>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(None, query::<Option<i64>>(&db, "select crc32(null)")?);
+        Ok(())
+    }
+
+    // Pinned so that dependency updates can't silently change how existing
+    // datasets are partitioned.
+    #[test]
+    fn xxh3_64_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            ("select xxh3_64(x'')", 0x2d06_8005_38d3_94c2),
+            ("select xxh3_64('abc')", 0x78af_5f94_892f_3950),
+            ("select xxh3_64(x'cafe')", 0x011b_36c9_ac57_bcda),
+            ("select xxh3_64(x'', 42)", -5_752_995_443_491_633_966),
+            ("select xxh3_64('abc', 42)", -2_863_288_879_874_843_453),
+            ("select xxh3_64(x'cafe', 42)", 3_117_999_598_855_916_444),
+            ("select xxh3_64(x'cafe', -1)", -4_264_029_023_383_697_662),
+            ("select xxh3_64(x'cafe', 0)", 0x011b_36c9_ac57_bcda),
+        ] {
+            assert_eq!(*expected, query::<i64>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            -6_309_269_121_238_725_342,
+            db.query_row(
+                "select xxh3_64(?)",
+                rusqlite::params![(0..1024).map(|i| i as u8).collect::<Vec<u8>>()],
+                |r| r.get::<usize, i64>(0),
+            )?
+        );
+        assert_eq!(None, query::<Option<i64>>(&db, "select xxh3_64(null)")?);
+        assert_eq!(
+            None,
+            query::<Option<i64>>(&db, "select xxh3_64(x'', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sha512sum_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        assert_eq!(
+            "8DE6C23D6CFCDE8E3D304FEB564E69EF2E6CD3F4622A6CE549A884FC36F7AC59\
+             E8E8CB96C9BF7367D2B90C033C30A1A6B24FB379802EC18612EC50CCAC2048EC",
+            query::<String>(&db, "select hex(sha512sum(x'cafe'))")?
+        );
+        assert_eq!(64, query::<i64>(&db, "select length(sha512sum(x''))")?);
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select sha512sum(null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_compress_blob_roundtrips() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for sql in &[
+            "select zstd_decompress_blob(zstd_compress_blob(x'cafe'))",
+            "select zstd_decompress_blob(zstd_compress_blob(x'cafe', 1))",
+            "select zstd_decompress_blob(zstd_compress_blob(x'cafe', 19))",
+            "select zstd_decompress_blob(zstd_compress_blob(x'cafe', 3, false))",
+        ] {
+            assert_eq!(vec![0xca, 0xfe], query::<Vec<u8>>(&db, sql)?, "{}", sql);
+        }
+        assert_eq!(
+            b"chia".to_vec(),
+            query::<Vec<u8>>(
+                &db,
+                "select zstd_decompress_blob(zstd_compress_blob('chia'))"
+            )?
+        );
+        assert_eq!(
+            0,
+            query::<i64>(
+                &db,
+                "select length(zstd_decompress_blob(zstd_compress_blob(x'')))"
+            )?
+        );
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select zstd_compress_blob(x'cafe', null)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_compress_blob_selects_level_and_header() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let data = (0..20_000)
+            .map(|i| format!("{{\"height\": {}, \"amount\": {}}},", i, i * 7919 % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let compress =
+            |sql: &str| db.query_row(sql, rusqlite::params![data], |r| r.get::<usize, Vec<u8>>(0));
+        let fast = compress("select zstd_compress_blob(?, 1)")?;
+        let strong = compress("select zstd_compress_blob(?, 19)")?;
+        assert_ne!(fast, strong);
+        for frame in &[&fast, &strong] {
+            assert_eq!(data, zstd::stream::decode_all(&frame[..])?);
+        }
+
+        // Bit 5 (single segment) or bits 6-7 (size field length) of the frame
+        // header descriptor are set iff the content size is recorded.
+        let with_size = compress("select zstd_compress_blob(?, 3, true)")?;
+        let without_size = compress("select zstd_compress_blob(?, 3, false)")?;
+        assert_ne!(0, with_size[4] & 0xe0);
+        assert_eq!(0, without_size[4] & 0xe0);
+        assert_eq!(with_size, compress("select zstd_compress_blob(?, 3)")?);
+        assert_eq!(data, zstd::stream::decode_all(&without_size[..])?);
+
+        let range = zstd::compression_level_range();
+        for level in &[*range.end() as i64 + 1, *range.start() as i64 - 1] {
+            assert_eq!(
+                format!(
+                    "zstd level must be between {} and {}, got {}",
+                    range.start(),
+                    range.end(),
+                    level
+                ),
+                query_err(
+                    &db,
+                    &format!("select zstd_compress_blob(x'cafe', {})", level)
+                )
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_frame_content_size_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        for (sql, expected) in &[
+            (
+                "select zstd_frame_content_size(x'28b52ffd2002110000cafe')",
+                Some(2),
+            ),
+            (
+                "select zstd_frame_content_size('28b52ffd2002110000cafe')",
+                Some(2),
+            ),
+            (
+                "select zstd_frame_content_size(zstd_compress_blob(zeroblob(1000000)))",
+                Some(1_000_000),
+            ),
+            (
+                "select zstd_frame_content_size(zstd_compress_blob(zeroblob(1000000), 3, false))",
+                None,
+            ),
+            ("select zstd_frame_content_size(null)", None),
+        ] {
+            assert_eq!(*expected, query::<Option<i64>>(&db, sql)?, "{}", sql);
+        }
+        // The header is enough, the frame needn't be complete.
+        assert_eq!(
+            2,
+            query::<i64>(&db, "select zstd_frame_content_size(x'28b52ffd2002')")?
+        );
+        for sql in &[
+            "select zstd_frame_content_size(x'cafe')",
+            "select zstd_frame_content_size(x'28b52ffd')",
+        ] {
+            assert_eq!(
+                "invalid or truncated zstd frame header",
+                query_err(&db, sql),
+                "{}",
+                sql
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn zstd_decompress_with_dict_works() -> anyhow::Result<()> {
+        let db = open_db()?;
+        let dict =
+            b"{\"coin\": {\"parent_coin_info\": \"0x\", \"puzzle_hash\": \"0x\", \"amount\": 0}}"
+                .to_vec();
+        let payload = b"{\"coin\": {\"parent_coin_info\": \"0xcafe\", \"puzzle_hash\": \"0xf00d\", \"amount\": 1}}".to_vec();
+        let compressed = zstd::bulk::Compressor::with_dictionary(3, &dict)?.compress(&payload)?;
+        let decompress = |sql: &str| {
+            db.query_row(sql, rusqlite::params![compressed, dict], |r| {
+                r.get::<usize, Vec<u8>>(0)
+            })
+        };
+        assert_eq!(
+            payload,
+            decompress("select zstd_decompress_with_dict(?, ?)")?
+        );
+        assert_eq!(
+            payload,
+            decompress("select zstd_decompress_with_dict(?, ?, 1000)")?
+        );
+        assert!(decompress("select zstd_decompress_with_dict(?, ?, 10)")
+            .unwrap_err()
+            .to_string()
+            .contains("decompressed output exceeds 10 bytes"));
+        // Without the dictionary, the frame can't be decompressed.
+        assert!(decompress("select zstd_decompress_blob(?) where ? is not null").is_err());
+        assert_eq!(
+            None,
+            query::<Option<Vec<u8>>>(&db, "select zstd_decompress_with_dict(x'', null)")?
+        );
+        Ok(())
+    }
+
     #[test]
     fn zstd_decompress_blob_works() -> anyhow::Result<()> {
         let db = open_db()?;
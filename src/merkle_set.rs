@@ -0,0 +1,201 @@
+// Chia's merkle set, which backs the additions and removals roots of a block.
+// Items sit in a radix trie keyed by their bits, except that a pair of items
+// hashes the same no matter how long a prefix the two share.
+
+use std::convert::TryFrom;
+
+use sha2::{Digest, Sha256};
+
+const BLANK: [u8; 32] = [0; 32];
+
+#[derive(Clone, Copy)]
+enum NodeType {
+    Empty,
+    Term,
+    Mid,
+}
+
+impl NodeType {
+    fn encode(self) -> u8 {
+        match self {
+            NodeType::Empty => 0,
+            NodeType::Term => 1,
+            NodeType::Mid => 2,
+        }
+    }
+}
+
+fn hash_node(left: (NodeType, &[u8; 32]), right: (NodeType, &[u8; 32])) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0; 30]);
+    hasher.update([left.0.encode(), right.0.encode()]);
+    hasher.update(left.1);
+    hasher.update(right.1);
+    hasher.finalize().into()
+}
+
+fn bit(item: &[u8; 32], depth: usize) -> bool {
+    item[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// Computes the root of the set of `items`. Duplicates count once, like
+/// adding an item to the set twice.
+pub fn root(items: &mut Vec<[u8; 32]>) -> [u8; 32] {
+    items.sort_unstable();
+    items.dedup();
+    let (node_type, hash) = subtree(items, 0);
+    compress_root(node_type, hash)
+}
+
+/// Turns the top node into the root; a lone item is hashed once more.
+fn compress_root(node_type: NodeType, hash: [u8; 32]) -> [u8; 32] {
+    match node_type {
+        NodeType::Term => {
+            let mut hasher = Sha256::new();
+            hasher.update([NodeType::Term.encode()]);
+            hasher.update(hash);
+            hasher.finalize().into()
+        }
+        _ => hash,
+    }
+}
+
+/// Hashes the subtree holding `items`, which are sorted, distinct and share
+/// their first `depth` bits.
+fn subtree(items: &[[u8; 32]], depth: usize) -> (NodeType, [u8; 32]) {
+    match items {
+        [] => (NodeType::Empty, BLANK),
+        [item] => (NodeType::Term, *item),
+        [a, b] => (
+            NodeType::Mid,
+            hash_node((NodeType::Term, a), (NodeType::Term, b)),
+        ),
+        _ => {
+            let split = items.partition_point(|item| !bit(item, depth));
+            let (left, right) = items.split_at(split);
+            let (left_type, left_hash) = subtree(left, depth + 1);
+            let (right_type, right_hash) = subtree(right, depth + 1);
+            (
+                NodeType::Mid,
+                hash_node((left_type, &left_hash), (right_type, &right_hash)),
+            )
+        }
+    }
+}
+
+// Proofs serialize the path to an item depth-first: 0x00 for an empty node,
+// 0x01 and a 32-byte item for a terminal, 0x02 followed by both children for
+// a middle node and 0x03 and a 32-byte hash for a truncated subtree. Pairs
+// next to an empty sibling are spelled out, so their hash can be recomputed.
+const EMPTY: u8 = 0;
+const TERMINAL: u8 = 1;
+const MIDDLE: u8 = 2;
+const TRUNCATED: u8 = 3;
+
+enum ProofNode {
+    Empty,
+    Term([u8; 32]),
+    Truncated([u8; 32]),
+    Mid {
+        hash: [u8; 32],
+        /// Whether exactly two items are below this node.
+        double: bool,
+        children: Box<[ProofNode; 2]>,
+    },
+}
+
+impl ProofNode {
+    fn hashed(&self) -> (NodeType, &[u8; 32]) {
+        match self {
+            ProofNode::Empty => (NodeType::Empty, &BLANK),
+            ProofNode::Term(item) => (NodeType::Term, item),
+            ProofNode::Truncated(hash) | ProofNode::Mid { hash, .. } => (NodeType::Mid, hash),
+        }
+    }
+}
+
+/// Checks a merkle set proof, as returned by the `request_additions` and
+/// `request_removals` wallet protocol messages, against `root`. Returns true
+/// if the proof is well-formed and shows `item` to be included, or excluded
+/// if `included` is false.
+pub fn verify_proof(root: &[u8; 32], item: &[u8; 32], proof: &[u8], included: bool) -> bool {
+    let mut pos = 0;
+    let tree = match parse_proof(proof, &mut pos, [0; 32], 0) {
+        Some(tree) if pos == proof.len() => tree,
+        _ => return false,
+    };
+    let (node_type, hash) = tree.hashed();
+    if compress_root(node_type, *hash) != *root {
+        return false;
+    }
+    let mut node = &tree;
+    let mut depth = 0;
+    loop {
+        match node {
+            ProofNode::Empty => return !included,
+            ProofNode::Term(term) => return (term == item) == included,
+            // The proof doesn't show what's below the path to `item`.
+            ProofNode::Truncated(_) => return false,
+            ProofNode::Mid { children, .. } => {
+                node = &children[usize::from(bit(item, depth))];
+                depth += 1;
+            }
+        }
+    }
+}
+
+/// Parses the node at `pos`, reached by the first `depth` bits of `path`.
+/// Returns None for malformed proofs, including subtrees the set itself
+/// would never contain.
+fn parse_proof(proof: &[u8], pos: &mut usize, path: [u8; 32], depth: usize) -> Option<ProofNode> {
+    let node_type = *proof.get(*pos)?;
+    *pos += 1;
+    match node_type {
+        EMPTY => Some(ProofNode::Empty),
+        TERMINAL => {
+            let item = read_hash(proof, pos)?;
+            if (0..depth).any(|i| bit(&item, i) != bit(&path, i)) {
+                return None;
+            }
+            Some(ProofNode::Term(item))
+        }
+        TRUNCATED => Some(ProofNode::Truncated(read_hash(proof, pos)?)),
+        MIDDLE if depth < 256 => {
+            let mut right_path = path;
+            right_path[depth / 8] |= 0x80 >> (depth % 8);
+            let left = parse_proof(proof, pos, path, depth + 1)?;
+            let right = parse_proof(proof, pos, right_path, depth + 1)?;
+            middle(left, right)
+        }
+        _ => None,
+    }
+}
+
+fn read_hash(proof: &[u8], pos: &mut usize) -> Option<[u8; 32]> {
+    let hash = <[u8; 32]>::try_from(proof.get(*pos..*pos + 32)?).ok()?;
+    *pos += 32;
+    Some(hash)
+}
+
+fn middle(left: ProofNode, right: ProofNode) -> Option<ProofNode> {
+    let (hash, double) = match (&left, &right) {
+        (ProofNode::Empty, ProofNode::Empty)
+        | (ProofNode::Empty, ProofNode::Term(_))
+        | (ProofNode::Term(_), ProofNode::Empty) => return None,
+        (ProofNode::Empty, ProofNode::Mid { hash, double, .. })
+        | (ProofNode::Mid { hash, double, .. }, ProofNode::Empty)
+            if *double =>
+        {
+            (*hash, true)
+        }
+        (ProofNode::Term(_), ProofNode::Term(_)) => {
+            (hash_node(left.hashed(), right.hashed()), true)
+        }
+        _ => (hash_node(left.hashed(), right.hashed()), false),
+    };
+    Some(ProofNode::Mid {
+        hash,
+        double,
+        children: Box::new([left, right]),
+    })
+}